@@ -1,16 +1,28 @@
 #![deny(elided_lifetimes_in_paths)]
 mod quay_configurator;
 use chrono::Utc;
-use clap::{Args, Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use core::panic;
 use env_logger::{fmt::Color, Env, Target};
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use indicatif_log_bridge::LogWrapper;
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::time::Instant;
 //use console_subscriber;
-use crate::quay_configurator::quay_config_reader::QuayXmlConfig;
-use env_logger;
-use log::{error, info, Level};
-use std::io::Write;
+use crate::quay_configurator::organization_plan::DeleteSelection;
+use crate::quay_configurator::organization_preflight::Operations;
+use crate::quay_configurator::organization_schema;
+use crate::quay_configurator::qcli_config::{self, QcliConfig, CONFIG_ENV};
+use crate::quay_configurator::quay_config_reader::{
+    LoginArguments, QuayXmlConfig, QuayXmlConfigArguments, TlsOptions,
+};
+use crate::quay_configurator::retry_policy::RetryPolicy;
+use crate::quay_configurator::run_report::OutputFormat;
+use log::{error, info, warn, Level};
+use std::io::{IsTerminal, Write};
 
 #[derive(Parser)]
 #[command(author, version, about="Quay batch processing cli written in Rust", long_about = None)]
@@ -28,10 +40,41 @@ struct Cli {
     command: SubCommands,
 
     #[arg(short, long)]
-    /// Quay yaml directory [REQUIRED]
-    dir: String,
-
-    #[arg(short, long)]
+    /// Quay yaml directory [REQUIRED], except for schema
+    dir: Option<String>,
+
+    #[arg(long, value_parser = parse_var)]
+    /// Template variable of the yaml files, as <name>=<value>, overriding their vars block. Can be repeated
+    var: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        env = "QCLI_STRICT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    /// Reject the fields of the yaml files not in the organization schema, as misspelled ones, instead of ignoring them.
+    /// --strict=false ignores them even if the config file sets strict. Default to strict of the config file, false if not set
+    strict: Option<bool>,
+
+    #[arg(long, env = CONFIG_ENV)]
+    /// TOML config file with the defaults of log_level, output, timeout, tls_verify, ca_bundle, client_cert, client_key,
+    /// rate_limit, concurrency, endpoint_concurrency, login_file, context and strict. Options, and their env variables, override it.
+    /// Default to qcli.toml of $XDG_CONFIG_HOME/qcli, or ~/.config/qcli, if it exists
+    config: Option<String>,
+
+    #[arg(long, env = "QCLI_LOGIN_FILE")]
+    /// Login file with the Quay tokens and the mirror passwords. Default to login_file of the config file,
+    /// then to .qcli/login.yaml if it exists in the current directory, then to login.yaml of $XDG_CONFIG_HOME/qcli
+    login_file: Option<PathBuf>,
+
+    #[arg(long, env = "QCLI_CONTEXT")]
+    /// Context of the login file, redirecting the quay_endpoint of the yaml files to the endpoints of an environment,
+    /// with their own tokens. Default to context of the config file, then to the context set with qcli context use
+    context: Option<String>,
+
+    #[arg(short, long, env = "QCLI_LOG_LEVEL")]
     /// Log level. Accepted log level: info, debug. Default to info.
     log_level: Option<log::Level>,
 
@@ -39,39 +82,231 @@ struct Cli {
     /// Log verbosity. Accepted values: 0,5,10. Default to 0
     verbosity: Option<u8>,
 
-    #[arg(long)]
+    #[arg(long, env = "QCLI_TIMEOUT")]
     /// Connection timeout in seconds. Default to 5
     timeout: Option<u64>,
 
-    #[arg(long)]
-    /// Verify Quay tls certificate. Default to true
+    #[arg(long, env = "QCLI_TLS_VERIFY")]
+    /// Verify Quay tls certificate, overriding quay_validate_certs of the yaml files. Default to quay_validate_certs, true if not set
     tls_verify: Option<bool>,
+
+    #[arg(long, env = "QCLI_CA_BUNDLE")]
+    /// PEM bundle of CAs trusted besides the system ones, unless a ca_file is set for the endpoint in login file
+    ca_bundle: Option<String>,
+
+    #[arg(long, requires = "client_key", env = "QCLI_CLIENT_CERT")]
+    /// PEM client certificate for Quay endpoints requiring mutual TLS, unless a client_cert is set for the endpoint in login file
+    client_cert: Option<String>,
+
+    #[arg(long, requires = "client_cert", env = "QCLI_CLIENT_KEY")]
+    /// PEM private key of the client certificate
+    client_key: Option<String>,
+
+    #[arg(long, env = "QCLI_RATE_LIMIT")]
+    /// Max requests per second sent to each Quay endpoint, unless a rate_limit is set for the endpoint in login file. Default to 5
+    rate_limit: Option<u32>,
+
+    #[arg(long, env = "QCLI_CONCURRENCY")]
+    /// Max number of in flight requests, and of organizations processed at the same time. Default to 50
+    concurrency: Option<usize>,
+
+    #[arg(long, env = "QCLI_ENDPOINT_CONCURRENCY")]
+    /// Max number of in flight requests for each Quay endpoint. Default to 10
+    endpoint_concurrency: Option<usize>,
+
+    #[arg(long, value_enum, env = "QCLI_OUTPUT")]
+    /// Format of the create, delete and apply run report printed to stdout. With json and junit, logs are written to stderr. Default to text
+    output: Option<OutputFormat>,
+
+    #[arg(long)]
+    /// Stop at the first failed request. By default failures are reported at the end of the run
+    fail_fast: bool,
+
+    #[arg(long)]
+    /// Do not check tokens and permissions before running. By default organizations whose token is rejected,
+    /// or lacks the permissions needed by the command, are reported and skipped before any write
+    skip_preflight: bool,
+
+    #[arg(long)]
+    /// Max number of attempts of a request failed with connection errors, timeouts, 429 or 5xx. Default to 4
+    max_attempts: Option<u32>,
+
+    #[arg(long)]
+    /// Delay in milliseconds before the first retry, doubled at each attempt. Default to 500
+    retry_delay: Option<u64>,
+
+    #[arg(long)]
+    /// Max delay in milliseconds between two attempts, also capping the Retry-After delay sent by Quay. Default to 30000
+    retry_max_delay: Option<u64>,
 }
 
 #[derive(Subcommand)]
 enum SubCommands {
     /// Create all Quay organizations
     Create(Create),
-    /// Delete all Quay organizations
+    /// Delete Quay organizations, or selected repositories, robots, teams and team members
     Delete(Delete),
     /// Check all Quay organizations yaml files
     Check(Check),
     /// Login to detected Quay organizations
     Login(Login),
+    /// Show the changes needed to reconcile Quay organizations with the yaml files
+    Plan(Plan),
+    /// Apply the changes needed to reconcile Quay organizations with the yaml files
+    Apply(Apply),
+    /// Export live Quay organizations to yaml files
+    Export(Export),
+    /// Report the differences between Quay organizations and the yaml files, changing nothing.
+    /// Exit code is 2 if drift is detected
+    Drift(Drift),
+    /// List, select and define the contexts of the login file
+    #[command(subcommand)]
+    Context(ContextCommands),
+    /// Print the JSON Schema of the organization yaml files, for the editors
+    Schema(Schema),
+}
+
+#[derive(Subcommand)]
+enum ContextCommands {
+    /// List the contexts of the login file, with their endpoint remapping. The current one is marked with *
+    List,
+    /// Use the context when --context is not set. The default context uses the top level logins
+    Use {
+        /// Context name
+        name: String,
+    },
+    /// Add a context, or add endpoint remappings to it. Its tokens are asked by qcli --context <name> login
+    Set {
+        /// Context name
+        name: String,
+
+        #[arg(long, value_parser = parse_endpoint_mapping)]
+        /// Quay endpoint of the yaml files redirected to an endpoint of the context, as <yaml endpoint>=<endpoint>.
+        /// Can be repeated
+        endpoint: Vec<(String, String)>,
+    },
 }
 
 #[derive(Args)]
-struct Login {}
+struct Login {
+    #[arg(long)]
+    /// Encrypt the login file with a new passphrase, read from QCLI_LOGIN_NEW_PASSPHRASE or asked on the terminal.
+    /// Plain text login files are encrypted
+    rekey: bool,
+
+    #[arg(long, conflicts_with = "rekey")]
+    /// Print the decrypted login file, with tokens and passwords masked
+    show: bool,
+
+    #[arg(long, conflicts_with_all = ["rekey", "show"])]
+    /// Keep the existing login file, asking only the tokens and mirror passwords missing from it
+    merge: bool,
+
+    #[arg(long, conflicts_with_all = ["rekey", "show"])]
+    /// Read tokens and mirror passwords from a yaml or json document on stdin, with the login file format,
+    /// instead of asking them. The login file is recreated unless --merge is set
+    non_interactive: bool,
+}
 
 #[derive(Args)]
 struct Create {}
 
 #[derive(Args)]
-struct Delete {}
+struct Delete {
+    #[arg(long)]
+    /// Quay organization to delete or to delete objects from. Default to all organizations
+    org: Option<String>,
+
+    #[arg(long, requires = "org")]
+    /// Repository to delete. Can be repeated
+    repository: Vec<String>,
+
+    #[arg(long, requires = "org")]
+    /// Robot to delete. Can be repeated
+    robot: Vec<String>,
+
+    #[arg(long, requires = "org")]
+    /// Team to delete. Can be repeated
+    team: Vec<String>,
+
+    #[arg(long, requires = "org", value_parser = parse_team_member)]
+    /// Team member to delete, as <team>:<member>. Can be repeated
+    team_member: Vec<(String, String)>,
+}
+
+/// Parses a '<team>:<member>' team member selector.
+fn parse_team_member(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((team, member)) if !team.is_empty() && !member.is_empty() => {
+            Ok((team.to_string(), member.to_string()))
+        }
+        _ => Err(format!("'{}' is not in <team>:<member> format", s)),
+    }
+}
+
+/// Parses a '<name>=<value>' template variable.
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("'{}' is not in <name>=<value> format", s)),
+    }
+}
+
+/// Parses a '<yaml endpoint>=<endpoint>' endpoint remapping.
+fn parse_endpoint_mapping(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => Err(format!(
+            "'{}' is not in <yaml endpoint>=<endpoint> format",
+            s
+        )),
+    }
+}
 
 #[derive(Args)]
 struct Check {}
 
+#[derive(Args)]
+struct Plan {}
+
+#[derive(Args)]
+struct Apply {
+    #[arg(long)]
+    /// Apply the plan without asking for confirmation
+    auto_approve: bool,
+}
+
+#[derive(Args)]
+struct Export {
+    #[arg(long)]
+    /// Quay endpoint to export. Default to all endpoints found in login file
+    endpoint: Option<String>,
+
+    #[arg(long)]
+    /// Quay organization to export. Default to all organizations administered by the token
+    org: Option<String>,
+
+    #[arg(long)]
+    /// Overwrite existing yaml files
+    overwrite: bool,
+}
+
+#[derive(Args)]
+struct Schema {
+    #[arg(long)]
+    /// Write the schema to the file. Default to stdout
+    file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Drift {
+    #[arg(long, default_value = "qcli-drift.json")]
+    /// Path of the JSON drift report
+    report: String,
+}
+
 /// qr async main
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -82,84 +317,161 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let now = Instant::now();
 
     let cli = Cli::parse();
-    let req_per_seconds = 300;
-
-    let log_level: log::Level;
-    match cli.log_level {
-        Some(ll) => log_level = ll,
-        None => log_level = log::Level::Info,
-    }
-
-    let log_verbosity: u8;
-    match cli.verbosity {
-        Some(verbosity) => log_verbosity = verbosity,
-        None => log_verbosity = 0,
-    }
 
-    let timeout: u64;
-    match cli.timeout {
-        Some(to) => timeout = to,
-        None => timeout = 5,
+    // The schema is printed alone, without logs, to be redirected to a file
+    if let SubCommands::Schema(schema) = &cli.command {
+        let json = serde_json::to_string_pretty(&organization_schema::organization_schema())?;
+        match &schema.file {
+            Some(file) => std::fs::write(file, json + "\n")
+                .map_err(|e| format!("Unable to write {}: {}", file.display(), e))?,
+            None => println!("{}", json),
+        }
+        return Ok(());
     }
 
-    let tls_verify: bool;
-    match cli.tls_verify {
-        Some(verify) => tls_verify = verify,
-        None => tls_verify = true,
-    }
+    let dir = match &cli.dir {
+        Some(dir) => dir.clone(),
+        None => Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  --dir <DIR>",
+            )
+            .exit(),
+    };
+
+    // Options and env variables first, then the config file, then the built-in defaults
+    let qcli_config = QcliConfig::load(cli.config.as_deref())?;
+
+    let log_level = cli
+        .log_level
+        .or(qcli_config.log_level)
+        .unwrap_or(log::Level::Info);
+
+    let log_verbosity = cli.verbosity.unwrap_or(0);
+
+    let timeout = cli.timeout.or(qcli_config.timeout).unwrap_or(5);
+
+    // Client certificate and key are never taken one from the command line, one from the config file
+    let (client_cert, client_key) = match cli.client_cert.is_some() {
+        true => (cli.client_cert.clone(), cli.client_key.clone()),
+        false => (qcli_config.client_cert, qcli_config.client_key),
+    };
+    let tls = TlsOptions {
+        verify: cli.tls_verify.or(qcli_config.tls_verify),
+        ca_file: cli.ca_bundle.clone().or(qcli_config.ca_bundle),
+        client_cert,
+        client_key,
+    };
+
+    let output = cli
+        .output
+        .or(qcli_config.output)
+        .unwrap_or(OutputFormat::Text);
+
+    let requests_per_second = cli.rate_limit.or(qcli_config.rate_limit).unwrap_or(5);
+
+    let concurrency = cli.concurrency.or(qcli_config.concurrency).unwrap_or(50);
+
+    let endpoint_concurrency = cli
+        .endpoint_concurrency
+        .or(qcli_config.endpoint_concurrency)
+        .unwrap_or(10);
+
+    let login_file = cli
+        .login_file
+        .clone()
+        .or(qcli_config.login_file)
+        .unwrap_or_else(qcli_config::default_login_file);
+
+    let default_retry_policy = RetryPolicy::default();
+    let retry_policy = RetryPolicy {
+        max_attempts: cli
+            .max_attempts
+            .unwrap_or(default_retry_policy.max_attempts)
+            .max(1),
+        base_delay: cli
+            .retry_delay
+            .map_or(default_retry_policy.base_delay, Duration::from_millis),
+        max_delay: cli
+            .retry_max_delay
+            .map_or(default_retry_policy.max_delay, Duration::from_millis),
+    };
+
+    // Progress bars only on a terminal, plain log lines otherwise
+    let progress = (output == OutputFormat::Text && std::io::stdout().is_terminal())
+        .then(|| MultiProgress::with_draw_target(ProgressDrawTarget::stdout()));
 
     //env_logger::init_from_env(Env::default().default_filter_or(log_level.as_str()));
-    env_logger::Builder::from_env(Env::default().default_filter_or(log_level.as_str()))
-        .target(Target::Stdout)
-        .format(|buf, record| {
-            let mut level_style = buf.style();
-
-            match record.level() {
-                Level::Info => level_style.set_color(Color::Green).set_bold(true),
-                Level::Debug => level_style.set_color(Color::Blue).set_bold(true),
-                Level::Warn => level_style.set_color(Color::Yellow).set_bold(true),
-                Level::Error => level_style.set_color(Color::Red).set_bold(true),
-                Level::Trace => level_style.set_color(Color::Black).set_bold(true),
-            };
-
-            writeln!(
-                buf,
-                "[{} {}]: {}",
-                buf.timestamp(),
-                level_style.value(record.level()),
-                record.args()
-            )
-        })
-        .init();
+    let logger =
+        env_logger::Builder::from_env(Env::default().default_filter_or(log_level.as_str()))
+            .target(match output {
+                OutputFormat::Text => Target::Stdout,
+                _ => Target::Stderr,
+            })
+            .format(|buf, record| {
+                let mut level_style = buf.style();
+
+                match record.level() {
+                    Level::Info => level_style.set_color(Color::Green).set_bold(true),
+                    Level::Debug => level_style.set_color(Color::Blue).set_bold(true),
+                    Level::Warn => level_style.set_color(Color::Yellow).set_bold(true),
+                    Level::Error => level_style.set_color(Color::Red).set_bold(true),
+                    Level::Trace => level_style.set_color(Color::Black).set_bold(true),
+                };
+
+                writeln!(
+                    buf,
+                    "[{} {}]: {}",
+                    buf.timestamp(),
+                    level_style.value(record.level()),
+                    record.args()
+                )
+            })
+            .build();
+    let max_level = logger.filter();
+
+    match &progress {
+        // Log lines are printed above the progress bars
+        Some(multi) => LogWrapper::new(multi.clone(), logger).try_init()?,
+        None => log::set_boxed_logger(Box::new(logger))?,
+    }
+    log::set_max_level(max_level);
 
     info!("UTC start time: {:?}", time.to_rfc3339());
 
-    let mut config: QuayXmlConfig;
-
-    match QuayXmlConfig::new(
-        &cli.dir,
-        req_per_seconds,
+    let arguments = QuayXmlConfigArguments {
+        requests_per_second,
         log_level,
         log_verbosity,
         timeout,
-        false,
-        tls_verify,
-    ) {
+        tls,
+        concurrency,
+        endpoint_concurrency,
+        retry_policy,
+        fail_fast: cli.fail_fast,
+        output,
+        progress,
+        skip_preflight: cli.skip_preflight,
+        login_file,
+        context: cli.context.clone().or(qcli_config.context),
+        vars: cli.var.iter().cloned().collect(),
+        strict: cli.strict.or(qcli_config.strict).unwrap_or(false),
+    };
+
+    let mut config: QuayXmlConfig;
+
+    // The login and context subcommands write the login file, they do not need to unlock it
+    let ignore_login_config =
+        matches!(cli.command, SubCommands::Login(_) | SubCommands::Context(_));
+
+    match QuayXmlConfig::new(&dir, arguments.clone(), ignore_login_config) {
         Ok(c) => {
             config = c;
             info!("Basic config successfully loaded")
         }
-        Err(_e) => {
-            error!("Login config file not found or corrupted. Run qcli login.");
-            match QuayXmlConfig::new(
-                &cli.dir,
-                req_per_seconds,
-                log_level,
-                log_verbosity,
-                timeout,
-                true,
-                tls_verify,
-            ) {
+        Err(e) => {
+            error!("Unable to load login config: {}. Run qcli login.", e);
+            match QuayXmlConfig::new(&dir, arguments.clone(), true) {
                 Ok(c) => {
                     config = c;
                     info!("Dummy login config successfully loaded")
@@ -176,65 +488,194 @@ async fn main() -> Result<(), Box<dyn Error>> {
         SubCommands::Create(_) => {
             info!(
                 "Checking quay configurations file from {} directory...",
-                &cli.dir
+                &dir
             );
 
             config.check_config(true).await?;
 
             info!(
                 "Loading quay configurations file from {} directory...",
-                &cli.dir
+                &dir
             );
 
             config.load_config().await?;
 
+            config.preflight_all(Operations::Reconcile, None).await;
+
             info!("Creating quay configurations...");
 
             config.create_all().await?;
         }
-        SubCommands::Delete(_) => {
+        SubCommands::Delete(delete) => {
             info!(
                 "Checking quay configurations file from {} directory...",
-                &cli.dir
+                &dir
             );
 
             config.check_config(false).await?;
 
             info!(
                 "Loading quay configurations file from {} directory...",
-                &cli.dir
+                &dir
             );
 
             config.load_config().await?;
 
-            info!("Creating quay configurations...");
+            config
+                .preflight_all(Operations::Delete, delete.org.as_ref())
+                .await;
 
-            config.delete_all().await?;
+            info!("Deleting quay configurations...");
+
+            let selection = DeleteSelection {
+                repositories: delete.repository.clone(),
+                robots: delete.robot.clone(),
+                teams: delete.team.clone(),
+                team_members: delete.team_member.clone(),
+            };
+
+            config.delete_all(delete.org.as_ref(), &selection).await?;
         }
         SubCommands::Check(_) => {
             info!(
                 "Checking quay configurations file from {} directory...",
-                &cli.dir
+                &dir
+            );
+
+            config.check_config(true).await?;
+
+            info!(
+                "Loading quay configurations file from {} directory...",
+                &dir
+            );
+            config.load_config().await?;
+        }
+        SubCommands::Plan(_) => {
+            info!(
+                "Checking quay configurations file from {} directory...",
+                &dir
+            );
+
+            config.check_config(true).await?;
+
+            info!(
+                "Loading quay configurations file from {} directory...",
+                &dir
+            );
+
+            config.load_config().await?;
+
+            config.preflight_all(Operations::Read, None).await;
+
+            info!("Planning quay configurations...");
+
+            let plans = config.plan_all().await?;
+            config.print_plans(&plans);
+        }
+        SubCommands::Apply(apply) => {
+            info!(
+                "Checking quay configurations file from {} directory...",
+                &dir
             );
 
             config.check_config(true).await?;
 
             info!(
                 "Loading quay configurations file from {} directory...",
-                &cli.dir
+                &dir
             );
+
             config.load_config().await?;
+
+            config.preflight_all(Operations::Reconcile, None).await;
+
+            info!("Planning quay configurations...");
+
+            let plans = config.plan_all().await?;
+            config.print_plans(&plans);
+
+            if plans.iter().all(|p| p.is_empty()) {
+                info!("No changes to apply.");
+            } else if apply.auto_approve || QuayXmlConfig::confirm_plans()? {
+                info!("Applying quay configurations...");
+
+                config.apply_all(&plans).await?;
+            } else {
+                info!("Apply cancelled.");
+            }
+        }
+        SubCommands::Export(export) => {
+            info!("Exporting quay configurations to {} directory...", &dir);
+
+            config
+                .export_all(
+                    export.endpoint.as_ref(),
+                    export.org.as_ref(),
+                    export.overwrite,
+                )
+                .await?;
+        }
+        SubCommands::Drift(drift) => {
+            info!(
+                "Checking quay configurations file from {} directory...",
+                &dir
+            );
+
+            config.check_config(false).await?;
+
+            info!(
+                "Loading quay configurations file from {} directory...",
+                &dir
+            );
+
+            config.load_config().await?;
+
+            config.preflight_all(Operations::Read, None).await;
+
+            info!("Detecting quay configurations drift...");
+
+            let report = config.drift_all(&drift.report).await?;
+
+            if !report.unchecked.is_empty() {
+                error!(
+                    "{} organizations could not be checked.",
+                    report.unchecked.len()
+                );
+                std::process::exit(1);
+            }
+            if report.drift {
+                warn!("Drift detected: {} differences found.", report.count());
+                std::process::exit(2);
+            }
+            info!("No drift detected.");
+        }
+        SubCommands::Context(ContextCommands::List) => config.list_contexts()?,
+        SubCommands::Context(ContextCommands::Use { name }) => config.use_context(name)?,
+        SubCommands::Context(ContextCommands::Set { name, endpoint }) => {
+            config.set_context(name, endpoint)?
         }
-        SubCommands::Login(_) => {
-            info!("Creating Quay login info from {} directory...", &cli.dir);
+        // Handled before the logger is initialized
+        SubCommands::Schema(_) => {}
+        SubCommands::Login(login) if login.show => config.show_login()?,
+        SubCommands::Login(login) if login.rekey => config.rekey_login()?,
+        SubCommands::Login(login) => {
+            info!("Creating Quay login info from {} directory...", &dir);
             config.check_config(false).await?;
             config.load_config().await?;
-            config.create_login().await?;
+            config
+                .create_login(LoginArguments {
+                    merge: login.merge,
+                    non_interactive: login.non_interactive,
+                })
+                .await?;
         }
     }
 
     info!("Execution terminated.");
-    info!("Total execution time in seconds: {}",now.elapsed().as_secs_f32() );
+    info!(
+        "Total execution time in seconds: {}",
+        now.elapsed().as_secs_f32()
+    );
 
     Ok(())
 }
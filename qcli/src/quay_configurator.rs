@@ -1,2 +1,16 @@
+mod credential_source;
+pub(crate) mod login_store;
+mod organization_drift;
+pub(crate) mod organization_plan;
+pub(crate) mod organization_preflight;
+mod organization_scheduler;
+pub(crate) mod organization_schema;
+mod organization_state;
+mod organization_struct;
+mod organization_validation;
+pub(crate) mod qcli_config;
 pub(crate) mod quay_config_reader;
-mod organization_struct;
\ No newline at end of file
+pub(crate) mod retry_policy;
+mod run_progress;
+pub(crate) mod run_report;
+mod yaml_template;
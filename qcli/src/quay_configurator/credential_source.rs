@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use tokio::process::Command;
+
+/// Source of a Quay token or of a mirror password, read the first time a request needs it.
+///
+/// ```yaml
+/// quay_token_from:
+///   env: QUAY_TOKEN_PROD
+/// quay_token_from:
+///   file: /run/secrets/quay
+/// quay_token_from:
+///   command: ["pass", "show", "quay/prod"]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialSource {
+    /// Value of an env variable
+    Env(String),
+    /// Content of a file, without leading and trailing whitespaces
+    File(String),
+    /// First line of the standard output of a command, like `pass show`
+    Command(Vec<String>),
+}
+
+impl CredentialSource {
+    /// Reads the credential. Empty credentials are an error.
+    pub async fn resolve(&self) -> Result<String, Box<dyn Error>> {
+        let credential = match self {
+            CredentialSource::Env(name) => {
+                std::env::var(name).map_err(|e| format!("{}: {}", self, e))?
+            }
+            CredentialSource::File(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", self, e))?
+                .trim()
+                .to_string(),
+            CredentialSource::Command(args) => {
+                let (program, args) = args
+                    .split_first()
+                    .ok_or_else(|| format!("{}: empty command", self))?;
+                let output = Command::new(program)
+                    .args(args)
+                    .output()
+                    .await
+                    .map_err(|e| format!("{}: {}", self, e))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "{}: {}: {}",
+                        self,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )
+                    .into());
+                }
+                String::from_utf8(output.stdout)
+                    .map_err(|e| format!("{}: {}", self, e))?
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            }
+        };
+
+        if credential.is_empty() {
+            return Err(format!("{}: empty credential", self).into());
+        }
+        Ok(credential)
+    }
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialSource::Env(name) => write!(f, "env variable {}", name),
+            CredentialSource::File(path) => write!(f, "file {}", path),
+            CredentialSource::Command(args) => write!(f, "command '{}'", args.join(" ")),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::XChaCha20Poly1305;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+/// Env variable with the passphrase of the login store, asked on the terminal if not set.
+pub const PASSPHRASE_ENV: &str = "QCLI_LOGIN_PASSPHRASE";
+
+/// Env variable with the new passphrase used by `qcli login --rekey`.
+pub const NEW_PASSPHRASE_ENV: &str = "QCLI_LOGIN_NEW_PASSPHRASE";
+
+const FORMAT_VERSION: u32 = 1;
+const KDF: &str = "argon2id";
+const SALT_LEN: usize = 16;
+
+/// Encrypted login file.
+///
+/// The login yaml is encrypted with XChaCha20-Poly1305, the key is derived from the
+/// passphrase with Argon2id. Binary fields are base64 encoded.
+/// ```yaml
+/// qcli_login_store: 1
+/// kdf: argon2id
+/// m_cost: 19456
+/// t_cost: 2
+/// p_cost: 1
+/// salt: <base64>
+/// nonce: <base64>
+/// ciphertext: <base64>
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedLogin {
+    qcli_login_store: u32,
+    kdf: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedLogin {
+    fn key(&self, passphrase: &str, salt: &[u8]) -> Result<[u8; 32], Box<dyn Error>> {
+        if self.kdf != KDF {
+            return Err(format!("Unsupported login store key derivation '{}'", self.kdf).into());
+        }
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| format!("Invalid login store key derivation parameters: {}", e))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("Unable to derive the login store key: {}", e))?;
+        Ok(key)
+    }
+}
+
+/// Returns true if the login file content is an encrypted store.
+pub fn is_encrypted(content: &str) -> bool {
+    serde_yaml::from_str::<EncryptedLogin>(content).is_ok()
+}
+
+/// Encrypts the login yaml with the passphrase, returning the content of the login file.
+pub fn encrypt(login_yaml: &str, passphrase: &str) -> Result<String, Box<dyn Error>> {
+    let params = Params::default();
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let mut store = EncryptedLogin {
+        qcli_login_store: FORMAT_VERSION,
+        kdf: KDF.to_string(),
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        salt: BASE64.encode(salt),
+        nonce: String::new(),
+        ciphertext: String::new(),
+    };
+
+    let cipher = XChaCha20Poly1305::new(&store.key(passphrase, &salt)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, login_yaml.as_bytes())
+        .map_err(|_| "Unable to encrypt the login store")?;
+    store.nonce = BASE64.encode(nonce);
+    store.ciphertext = BASE64.encode(ciphertext);
+
+    Ok(serde_yaml::to_string(&store)?)
+}
+
+/// Decrypts the content of an encrypted login file, returning the login yaml.
+pub fn decrypt(content: &str, passphrase: &str) -> Result<String, Box<dyn Error>> {
+    let store: EncryptedLogin = serde_yaml::from_str(content)?;
+    if store.qcli_login_store != FORMAT_VERSION {
+        return Err(format!("Unsupported login store version {}", store.qcli_login_store).into());
+    }
+
+    let salt = BASE64.decode(&store.salt)?;
+    let nonce = BASE64.decode(&store.nonce)?;
+    let ciphertext = BASE64.decode(&store.ciphertext)?;
+    if nonce.len() != 24 {
+        return Err("Invalid login store nonce".into());
+    }
+
+    let cipher = XChaCha20Poly1305::new(&store.key(passphrase, &salt)?.into());
+    let login_yaml = cipher
+        .decrypt(nonce.as_slice().into(), ciphertext.as_slice())
+        .map_err(|_| "Unable to decrypt the login store: wrong passphrase or corrupted file")?;
+
+    Ok(String::from_utf8(login_yaml)?)
+}
+
+/// Reads the passphrase from the env variable, or asks it on the terminal without echo.
+/// With `confirm` the passphrase is asked twice.
+pub fn read_passphrase(env: &str, prompt: &str, confirm: bool) -> Result<String, Box<dyn Error>> {
+    if let Ok(passphrase) = std::env::var(env) {
+        if passphrase.is_empty() {
+            return Err(format!("{} is empty", env).into());
+        }
+        return Ok(passphrase);
+    }
+
+    let ask = |prompt: &str| {
+        rpassword::prompt_password(prompt)
+            .map_err(|e| format!("Unable to read the passphrase: {}. Set {}.", e, env))
+    };
+
+    let passphrase = ask(prompt)?;
+    if passphrase.is_empty() {
+        return Err("Empty passphrase".into());
+    }
+    if confirm && ask("Confirm passphrase: ")? != passphrase {
+        return Err("Passphrases do not match".into());
+    }
+
+    Ok(passphrase)
+}
+
+/// Reads the login file, decrypting it if encrypted.
+/// Returns the login yaml, and the passphrase if the file was encrypted.
+pub fn load(path: &Path) -> Result<(String, Option<String>), Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+
+    if !is_encrypted(&content) {
+        return Ok((content, None));
+    }
+
+    let passphrase = read_passphrase(PASSPHRASE_ENV, "Login store passphrase: ", false)?;
+    Ok((decrypt(&content, &passphrase)?, Some(passphrase)))
+}
+
+/// Encrypts the login yaml and writes it to the login file, readable by the owner only.
+pub fn save(path: &Path, login_yaml: &str, passphrase: &str) -> Result<(), Box<dyn Error>> {
+    let content = encrypt(login_yaml, passphrase)?;
+    write_private(path, &content)
+}
+
+/// Writes the login file, plain text or encrypted, readable by the owner only.
+pub fn write_private(path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).write(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode of an existing file is kept by open
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use super::organization_plan::{Change, FieldChange, OrganizationPlan};
+use super::organization_struct::OrganizationYaml;
+use chrono::Utc;
+use serde::Serialize;
+
+/// Machine readable report of the differences between the yaml files and the live Quay organizations.
+#[derive(Debug, Serialize)]
+pub struct DriftReport {
+    pub generated_at: String,
+    pub drift: bool,
+    pub organizations: Vec<OrganizationDrift>,
+    /// Organizations that could not be compared, for example because of a missing token.
+    pub unchecked: Vec<UncheckedOrganization>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationDrift {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+    pub differences: Vec<Difference>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UncheckedOrganization {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftKind {
+    /// Declared in the yaml files but not present in Quay
+    Missing,
+    /// Present in Quay with different settings
+    Changed,
+    /// Present in Quay but not declared in the yaml files
+    Extra,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Difference {
+    pub drift: DriftKind,
+    pub object_type: String,
+    pub object: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+}
+
+impl DriftReport {
+    pub fn new(plans: &[OrganizationPlan], unchecked: &[&OrganizationYaml]) -> DriftReport {
+        let organizations: Vec<OrganizationDrift> = plans
+            .iter()
+            .map(|plan| OrganizationDrift {
+                quay_endpoint: plan.organization.quay_endpoint.clone(),
+                quay_organization: plan.organization.quay_organization.clone(),
+                differences: plan
+                    .actions
+                    .iter()
+                    .map(|action| Difference {
+                        drift: match action.change {
+                            Change::Create => DriftKind::Missing,
+                            Change::Update => DriftKind::Changed,
+                            Change::Delete => DriftKind::Extra,
+                        },
+                        object_type: action.object.kind().to_string(),
+                        object: action.object.to_string(),
+                        fields: action.fields.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        DriftReport {
+            generated_at: Utc::now().to_rfc3339(),
+            drift: organizations.iter().any(|o| !o.differences.is_empty()),
+            organizations,
+            unchecked: unchecked
+                .iter()
+                .map(|org| UncheckedOrganization {
+                    quay_endpoint: org.quay_endpoint.clone(),
+                    quay_organization: org.quay_organization.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Total number of differences found.
+    pub fn count(&self) -> usize {
+        self.organizations.iter().map(|o| o.differences.len()).sum()
+    }
+}
@@ -0,0 +1,978 @@
+use super::organization_state::OWNERS_TEAM;
+use super::organization_struct::{
+    Actions, Members, MirrorParams, OrganizationYaml, PruneObject, QuayFnArguments, QuayResponse,
+    Repository, RobotDetails, Team, UserElement,
+};
+use log::info;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+/// Identifies the objects other objects depend on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ObjectKey {
+    Organization,
+    Robot(String),
+    Team(String),
+    Repository(String),
+}
+
+/// Kind of change needed to reconcile a Quay object with the yaml configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Create,
+    Update,
+    Delete,
+}
+
+impl Change {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Change::Create => "create",
+            Change::Update => "update",
+            Change::Delete => "delete",
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Change::Create => "+",
+            Change::Update => "~",
+            Change::Delete => "-",
+        }
+    }
+}
+
+/// Execution phases, in dependency order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Organization,
+    Robots,
+    Teams,
+    TeamMembers,
+    Repositories,
+    Permissions,
+    Mirrors,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 7] = [
+        Phase::Organization,
+        Phase::Robots,
+        Phase::Teams,
+        Phase::TeamMembers,
+        Phase::Repositories,
+        Phase::Permissions,
+        Phase::Mirrors,
+    ];
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            Phase::Organization => "organizations",
+            Phase::Robots => "robots",
+            Phase::Teams => "teams",
+            Phase::TeamMembers => "team members",
+            Phase::Repositories => "repositories",
+            Phase::Permissions => "repositories permissions",
+            Phase::Mirrors => "repositories mirror",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionKind {
+    User,
+    Robot,
+    Team,
+}
+
+impl fmt::Display for PermissionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            PermissionKind::User => "user",
+            PermissionKind::Robot => "robot",
+            PermissionKind::Team => "team",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Quay object touched by a plan action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanObject {
+    Organization,
+    Robot(RobotDetails),
+    Team(Team),
+    TeamMember {
+        team: String,
+        name: String,
+        is_robot: bool,
+    },
+    Repository(Repository),
+    Permission {
+        repository: String,
+        kind: PermissionKind,
+        permission: UserElement,
+    },
+    Mirror(Repository),
+}
+
+impl PlanObject {
+    /// Object type name used in reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PlanObject::Organization => "organization",
+            PlanObject::Robot(_) => "robot",
+            PlanObject::Team(_) => "team",
+            PlanObject::TeamMember { .. } => "team_member",
+            PlanObject::Repository(_) => "repository",
+            PlanObject::Permission { .. } => "permission",
+            PlanObject::Mirror(_) => "mirror",
+        }
+    }
+
+    /// Key of the object if other objects can depend on it.
+    pub fn key(&self) -> Option<ObjectKey> {
+        match self {
+            PlanObject::Organization => Some(ObjectKey::Organization),
+            PlanObject::Robot(robot) => Some(ObjectKey::Robot(robot.name.clone())),
+            PlanObject::Team(team) => Some(ObjectKey::Team(team.name.clone())),
+            PlanObject::Repository(repo) => Some(ObjectKey::Repository(repo.name.clone())),
+            _ => None,
+        }
+    }
+
+    /// Keys of the objects that must exist before this object can be created.
+    pub fn requirements(&self) -> Vec<ObjectKey> {
+        let mut requirements = vec![ObjectKey::Organization];
+        match self {
+            PlanObject::Organization => requirements.clear(),
+            PlanObject::TeamMember {
+                team,
+                name,
+                is_robot,
+            } => {
+                requirements.push(ObjectKey::Team(team.clone()));
+                if *is_robot {
+                    requirements.push(ObjectKey::Robot(name.clone()));
+                }
+            }
+            PlanObject::Permission {
+                repository,
+                kind,
+                permission,
+            } => {
+                requirements.push(ObjectKey::Repository(repository.clone()));
+                match kind {
+                    PermissionKind::Robot => {
+                        requirements.push(ObjectKey::Robot(permission.name.clone()))
+                    }
+                    PermissionKind::Team => {
+                        requirements.push(ObjectKey::Team(permission.name.clone()))
+                    }
+                    PermissionKind::User => {}
+                }
+            }
+            PlanObject::Mirror(repo) => {
+                requirements.push(ObjectKey::Repository(repo.name.clone()));
+                if let Some(params) = &repo.mirror_params {
+                    requirements.push(ObjectKey::Robot(params.robot_username.clone()));
+                }
+            }
+            PlanObject::Robot(_) | PlanObject::Team(_) | PlanObject::Repository(_) => {}
+        }
+        requirements
+    }
+
+    /// Object type as listed in quay_prune_objects, `None` if it can not be pruned.
+    pub fn prune_object(&self) -> Option<PruneObject> {
+        match self {
+            PlanObject::Robot(_) => Some(PruneObject::Robots),
+            PlanObject::Team(_) => Some(PruneObject::Teams),
+            PlanObject::TeamMember { .. } => Some(PruneObject::TeamMembers),
+            PlanObject::Repository(_) => Some(PruneObject::Repositories),
+            PlanObject::Permission { .. } => Some(PruneObject::Permissions),
+            PlanObject::Organization | PlanObject::Mirror(_) => None,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        match self {
+            PlanObject::Organization => Phase::Organization,
+            PlanObject::Robot(_) => Phase::Robots,
+            PlanObject::Team(_) => Phase::Teams,
+            PlanObject::TeamMember { .. } => Phase::TeamMembers,
+            PlanObject::Repository(_) => Phase::Repositories,
+            PlanObject::Permission { .. } => Phase::Permissions,
+            PlanObject::Mirror(_) => Phase::Mirrors,
+        }
+    }
+}
+
+impl fmt::Display for PlanObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanObject::Organization => write!(f, "organization"),
+            PlanObject::Robot(robot) => write!(f, "robot {}", robot.name),
+            PlanObject::Team(team) => write!(f, "team {}", team.name),
+            PlanObject::TeamMember {
+                team,
+                name,
+                is_robot,
+            } => {
+                let kind = if *is_robot { "robot" } else { "user" };
+                write!(f, "team {} {} member {}", team, kind, name)
+            }
+            PlanObject::Repository(repo) => write!(f, "repository {}", repo.name),
+            PlanObject::Permission {
+                repository,
+                kind,
+                permission,
+            } => write!(
+                f,
+                "repository {} {} permission {} ({})",
+                repository, kind, permission.name, permission.role
+            ),
+            PlanObject::Mirror(repo) => write!(f, "repository {} mirror", repo.name),
+        }
+    }
+}
+
+/// Single field difference of an updated object.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub actual: String,
+    pub wanted: String,
+}
+
+impl FieldChange {
+    fn compare(field: &str, actual: String, wanted: String, changes: &mut Vec<FieldChange>) {
+        if actual != wanted {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                actual,
+                wanted,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanAction {
+    pub change: Change,
+    pub object: PlanObject,
+    pub fields: Vec<FieldChange>,
+}
+
+impl PlanAction {
+    fn new(change: Change, object: PlanObject) -> PlanAction {
+        PlanAction {
+            change,
+            object,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.object.phase()
+    }
+
+    /// Executes the action against the organization's Quay endpoint.
+    pub async fn execute(
+        &self,
+        org: &OrganizationYaml,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        if self.change == Change::Delete {
+            info!(
+                "Deleting {} of organization '{}' on '{}'",
+                self.object, org.quay_organization, org.quay_endpoint
+            );
+        }
+
+        match (&self.change, &self.object) {
+            (
+                Change::Delete,
+                PlanObject::Permission {
+                    repository,
+                    kind,
+                    permission,
+                },
+            ) => match kind {
+                PermissionKind::User => {
+                    org.delete_user_permission_from_repository(
+                        repository,
+                        permission,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+                PermissionKind::Robot => {
+                    let robot = UserElement::new(
+                        format!("{}+{}", org.quay_organization, permission.name),
+                        permission.role.clone(),
+                    );
+                    org.delete_user_permission_from_repository(
+                        repository,
+                        &robot,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+                PermissionKind::Team => {
+                    org.delete_team_permission_from_repository(
+                        repository,
+                        permission,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+            },
+            (Change::Delete, PlanObject::Robot(robot)) => {
+                org.delete_robot(&robot.name, quay_fn_arguments).await
+            }
+            (Change::Delete, PlanObject::Team(team)) => {
+                org.delete_team(&team.name, quay_fn_arguments).await
+            }
+            (
+                Change::Delete,
+                PlanObject::TeamMember {
+                    team,
+                    name,
+                    is_robot,
+                },
+            ) => {
+                if *is_robot {
+                    org.delete_robot_from_team(team, name, quay_fn_arguments)
+                        .await
+                } else {
+                    org.delete_user_from_team(team, name, quay_fn_arguments)
+                        .await
+                }
+            }
+            (Change::Delete, PlanObject::Repository(repo)) => {
+                org.delete_repository(&repo.name, quay_fn_arguments).await
+            }
+            (Change::Delete, PlanObject::Organization) => {
+                org.delete_organization(quay_fn_arguments).await
+            }
+            (Change::Delete, object) => Err(format!("Deleting {} is not supported", object).into()),
+            (_, PlanObject::Organization) => org.create_organization(quay_fn_arguments).await,
+            (_, PlanObject::Robot(robot)) => org.create_robot(robot, quay_fn_arguments).await,
+            // Quay creates or updates teams with the same call.
+            (_, PlanObject::Team(team)) => org.create_team(team, quay_fn_arguments).await,
+            (
+                _,
+                PlanObject::TeamMember {
+                    team,
+                    name,
+                    is_robot,
+                },
+            ) => {
+                if *is_robot {
+                    org.add_robot_to_team(team, name, quay_fn_arguments).await
+                } else {
+                    org.add_user_to_team(team, name, quay_fn_arguments).await
+                }
+            }
+            (Change::Create, PlanObject::Repository(repo)) => {
+                org.create_repository(repo, quay_fn_arguments).await
+            }
+            (_, PlanObject::Repository(repo)) => {
+                org.update_repository(repo, quay_fn_arguments).await
+            }
+            (
+                _,
+                PlanObject::Permission {
+                    repository,
+                    kind,
+                    permission,
+                },
+            ) => match kind {
+                PermissionKind::User => {
+                    org.grant_user_permission_to_repository(
+                        repository,
+                        permission,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+                PermissionKind::Robot => {
+                    org.grant_robot_permission_to_repository(
+                        repository,
+                        permission,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+                PermissionKind::Team => {
+                    org.grant_team_permission_to_repository(
+                        repository,
+                        permission,
+                        quay_fn_arguments,
+                    )
+                    .await
+                }
+            },
+            // Mirror creation falls back to an update if the configuration already exists.
+            (_, PlanObject::Mirror(repo)) => {
+                org.create_repository_mirror(repo, quay_fn_arguments).await
+            }
+        }
+    }
+}
+
+impl fmt::Display for PlanAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  {} {}", self.change.symbol(), self.object)?;
+        for field in &self.fields {
+            write!(
+                f,
+                "\n      {}: {:?} -> {:?}",
+                field.field, field.actual, field.wanted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes needed to reconcile a live Quay organization with its yaml configuration.
+#[derive(Debug, Clone)]
+pub struct OrganizationPlan {
+    pub organization: OrganizationYaml,
+    pub actions: Vec<PlanAction>,
+    /// Creations of objects that already exist are reported as unchanged instead of failed.
+    /// Set when the plan is built without reading the live state.
+    pub existing_unchanged: bool,
+}
+
+impl OrganizationPlan {
+    /// Computes the plan comparing the wanted organization with its live state.
+    /// A `None` live state means the organization does not exist yet.
+    pub fn new(wanted: &OrganizationYaml, live: Option<&OrganizationYaml>) -> OrganizationPlan {
+        let mut actions = Vec::new();
+
+        if live.is_none() {
+            actions.push(PlanAction::new(Change::Create, PlanObject::Organization));
+        }
+
+        // Robots. Quay does not allow to update an existing robot, only missing ones are created.
+        for robot in &wanted.robots {
+            if !live.is_some_and(|l| l.robots.iter().any(|r| r.name == robot.name)) {
+                actions.push(PlanAction::new(
+                    Change::Create,
+                    PlanObject::Robot(robot.clone()),
+                ));
+            }
+        }
+
+        // Teams and team members
+        for team in &wanted.teams {
+            let live_team = live.and_then(|l| l.teams.iter().find(|t| t.name == team.name));
+            match live_team {
+                Some(live_team) => {
+                    let mut fields = Vec::new();
+                    FieldChange::compare(
+                        "description",
+                        live_team.description.clone(),
+                        team.description.clone(),
+                        &mut fields,
+                    );
+                    FieldChange::compare(
+                        "role",
+                        live_team.role.clone(),
+                        team.role.clone(),
+                        &mut fields,
+                    );
+                    if !fields.is_empty() {
+                        actions.push(PlanAction {
+                            change: Change::Update,
+                            object: PlanObject::Team(team.clone()),
+                            fields,
+                        });
+                    }
+                }
+                None => actions.push(PlanAction::new(
+                    Change::Create,
+                    PlanObject::Team(team.clone()),
+                )),
+            }
+
+            let members = team
+                .members
+                .users
+                .iter()
+                .map(|user| (user, false))
+                .chain(team.members.robots.iter().map(|robot| (robot, true)));
+            for (name, is_robot) in members {
+                let present = live_team.is_some_and(|t| {
+                    if is_robot {
+                        t.members.robots.contains(name)
+                    } else {
+                        t.members.users.contains(name)
+                    }
+                });
+                if !present {
+                    actions.push(PlanAction::new(
+                        Change::Create,
+                        PlanObject::TeamMember {
+                            team: team.name.clone(),
+                            name: name.clone(),
+                            is_robot,
+                        },
+                    ));
+                }
+            }
+
+            if let Some(live_team) = live_team {
+                let live_members = live_team
+                    .members
+                    .users
+                    .iter()
+                    .filter(|user| !team.members.users.contains(user))
+                    .map(|user| (user, false))
+                    .chain(
+                        live_team
+                            .members
+                            .robots
+                            .iter()
+                            .filter(|robot| !team.members.robots.contains(robot))
+                            .map(|robot| (robot, true)),
+                    );
+                for (name, is_robot) in live_members {
+                    actions.push(PlanAction::new(
+                        Change::Delete,
+                        PlanObject::TeamMember {
+                            team: team.name.clone(),
+                            name: name.clone(),
+                            is_robot,
+                        },
+                    ));
+                }
+            }
+        }
+
+        // Repositories, permissions and mirror configurations
+        for repo in &wanted.repositories {
+            let live_repo = live.and_then(|l| l.repositories.iter().find(|r| r.name == repo.name));
+
+            match live_repo {
+                Some(live_repo) => {
+                    let mut fields = Vec::new();
+                    FieldChange::compare(
+                        "description",
+                        live_repo.get_description().to_string(),
+                        repo.get_description().to_string(),
+                        &mut fields,
+                    );
+                    FieldChange::compare(
+                        "visibility",
+                        live_repo.get_visibility().to_string(),
+                        repo.get_visibility().to_string(),
+                        &mut fields,
+                    );
+                    if !fields.is_empty() {
+                        actions.push(PlanAction {
+                            change: Change::Update,
+                            object: PlanObject::Repository(repo.clone()),
+                            fields,
+                        });
+                    }
+                }
+                None => actions.push(PlanAction::new(
+                    Change::Create,
+                    PlanObject::Repository(repo.clone()),
+                )),
+            }
+
+            Self::plan_permissions(repo, live_repo, &mut actions);
+
+            let live_mirror_params = live_repo.and_then(|r| r.mirror_params.as_ref());
+            if let Some(mirror_params) = &repo.mirror_params {
+                match live_mirror_params {
+                    Some(live_mirror_params) => {
+                        let fields = Self::compare_mirror(live_mirror_params, mirror_params);
+                        if !fields.is_empty() {
+                            actions.push(PlanAction {
+                                change: Change::Update,
+                                object: PlanObject::Mirror(repo.clone()),
+                                fields,
+                            });
+                        }
+                    }
+                    None => actions.push(PlanAction::new(
+                        Change::Create,
+                        PlanObject::Mirror(repo.clone()),
+                    )),
+                }
+            } else if let Some(live_repo) = live_repo.filter(|_| live_mirror_params.is_some()) {
+                // Mirrors are never removed, they are only reported as drift
+                actions.push(PlanAction::new(
+                    Change::Delete,
+                    PlanObject::Mirror(live_repo.clone()),
+                ));
+            }
+        }
+
+        // Objects not declared in the yaml configuration
+        if let Some(live) = live {
+            for robot in &live.robots {
+                if !wanted.robots.iter().any(|r| r.name == robot.name) {
+                    actions.push(PlanAction::new(
+                        Change::Delete,
+                        PlanObject::Robot(robot.clone()),
+                    ));
+                }
+            }
+            for team in &live.teams {
+                if team.name != OWNERS_TEAM && !wanted.teams.iter().any(|t| t.name == team.name) {
+                    actions.push(PlanAction::new(
+                        Change::Delete,
+                        PlanObject::Team(team.clone()),
+                    ));
+                }
+            }
+            for repo in &live.repositories {
+                if !wanted.repositories.iter().any(|r| r.name == repo.name) {
+                    actions.push(PlanAction::new(
+                        Change::Delete,
+                        PlanObject::Repository(repo.clone()),
+                    ));
+                }
+            }
+        }
+
+        OrganizationPlan {
+            organization: wanted.clone(),
+            actions,
+            existing_unchanged: false,
+        }
+    }
+
+    /// Drops the deletions not allowed by the organization's quay_prune settings.
+    pub fn retain_prunable(&mut self) {
+        let organization = &self.organization;
+        self.actions.retain(|a| {
+            a.change != Change::Delete
+                || a.object
+                    .prune_object()
+                    .is_some_and(|object| organization.is_prunable(object))
+        });
+    }
+
+    fn plan_permissions(
+        repo: &Repository,
+        live_repo: Option<&Repository>,
+        actions: &mut Vec<PlanAction>,
+    ) {
+        let empty = Vec::new();
+        let wanted = repo.permissions.as_ref();
+        let live = live_repo.and_then(|r| r.permissions.as_ref());
+
+        let kinds = [
+            (
+                PermissionKind::User,
+                wanted.map_or(&empty, |p| &p.users),
+                live.map_or(&empty, |p| &p.users),
+            ),
+            (
+                PermissionKind::Robot,
+                wanted.map_or(&empty, |p| &p.robots),
+                live.map_or(&empty, |p| &p.robots),
+            ),
+            (
+                PermissionKind::Team,
+                wanted.and_then(|p| p.teams.as_ref()).unwrap_or(&empty),
+                live.and_then(|p| p.teams.as_ref()).unwrap_or(&empty),
+            ),
+        ];
+
+        for (kind, wanted_permissions, live_permissions) in kinds {
+            for permission in wanted_permissions {
+                let object = PlanObject::Permission {
+                    repository: repo.name.clone(),
+                    kind,
+                    permission: permission.clone(),
+                };
+                match live_permissions.iter().find(|p| p.name == permission.name) {
+                    Some(live_permission) if live_permission.role != permission.role => {
+                        actions.push(PlanAction {
+                            change: Change::Update,
+                            object,
+                            fields: vec![FieldChange {
+                                field: "role".to_string(),
+                                actual: live_permission.role.clone(),
+                                wanted: permission.role.clone(),
+                            }],
+                        });
+                    }
+                    Some(_) => {}
+                    None => actions.push(PlanAction::new(Change::Create, object)),
+                }
+            }
+
+            // If there is not a wanted user, Quay adds a single admin user so it must not be removed.
+            if kind == PermissionKind::User && wanted.is_none() {
+                continue;
+            }
+
+            for permission in live_permissions {
+                if !wanted_permissions.iter().any(|p| p.name == permission.name) {
+                    actions.push(PlanAction::new(
+                        Change::Delete,
+                        PlanObject::Permission {
+                            repository: repo.name.clone(),
+                            kind,
+                            permission: permission.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    fn compare_mirror(actual: &MirrorParams, wanted: &MirrorParams) -> Vec<FieldChange> {
+        let mut fields = Vec::new();
+        let option = |value: &Option<String>| value.clone().unwrap_or_default();
+
+        FieldChange::compare(
+            "src_registry",
+            actual.src_registry.clone(),
+            wanted.src_registry.clone(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "src_image",
+            actual.src_image.clone(),
+            wanted.src_image.clone(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "src_image_tags",
+            actual.src_image_tags.join(","),
+            wanted.src_image_tags.join(","),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "ext_registry_username",
+            option(&actual.ext_registry_username),
+            option(&wanted.ext_registry_username),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "ext_registry_verify_tls",
+            actual.ext_registry_verify_tls.to_string(),
+            wanted.ext_registry_verify_tls.to_string(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "ext_registry_unsigned_image",
+            actual
+                .ext_registry_unsigned_image
+                .unwrap_or_default()
+                .to_string(),
+            wanted
+                .ext_registry_unsigned_image
+                .unwrap_or_default()
+                .to_string(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "robot_username",
+            actual.robot_username.clone(),
+            wanted.robot_username.clone(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "sync_interval",
+            actual.sync_interval.to_string(),
+            wanted.sync_interval.to_string(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "is_enabled",
+            actual.is_enabled.to_string(),
+            wanted.is_enabled.to_string(),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "https_proxy",
+            option(&actual.https_proxy),
+            option(&wanted.https_proxy),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "http_proxy",
+            option(&actual.http_proxy),
+            option(&wanted.http_proxy),
+            &mut fields,
+        );
+        FieldChange::compare(
+            "no_proxy",
+            option(&actual.no_proxy),
+            option(&wanted.no_proxy),
+            &mut fields,
+        );
+
+        fields
+    }
+
+    /// Number of actions for the given change kind.
+    pub fn count(&self, change: Change) -> usize {
+        self.actions.iter().filter(|a| a.change == change).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl fmt::Display for OrganizationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Organization '{}' on endpoint '{}':",
+            self.organization.quay_organization, self.organization.quay_endpoint
+        )?;
+
+        if self.is_empty() {
+            return writeln!(f, "  No changes. Organization is up-to-date.");
+        }
+
+        for phase in Phase::ALL {
+            for action in self.actions.iter().filter(|a| a.phase() == phase) {
+                writeln!(f, "{}", action)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "  Plan: {} to add, {} to change, {} to remove.",
+            self.count(Change::Create),
+            self.count(Change::Update),
+            self.count(Change::Delete)
+        )
+    }
+}
+
+/// Objects of a single organization selected by `qcli delete`.
+#[derive(Debug, Default, Clone)]
+pub struct DeleteSelection {
+    pub repositories: Vec<String>,
+    pub robots: Vec<String>,
+    pub teams: Vec<String>,
+    /// Team members as (team, member).
+    pub team_members: Vec<(String, String)>,
+}
+
+impl DeleteSelection {
+    pub fn is_empty(&self) -> bool {
+        self.repositories.is_empty()
+            && self.robots.is_empty()
+            && self.teams.is_empty()
+            && self.team_members.is_empty()
+    }
+}
+
+impl OrganizationPlan {
+    /// Builds the plan deleting the selected objects of the organization.
+    /// An empty selection deletes the whole organization.
+    ///
+    /// A team member is a robot if it is named '<organization>+<robot>'
+    /// or if it is one of the robots declared in the yaml configuration.
+    pub fn deletions(
+        organization: &OrganizationYaml,
+        selection: &DeleteSelection,
+    ) -> OrganizationPlan {
+        let mut actions = Vec::new();
+
+        if selection.is_empty() {
+            actions.push(PlanAction::new(Change::Delete, PlanObject::Organization));
+        }
+
+        for name in &selection.robots {
+            actions.push(PlanAction::new(
+                Change::Delete,
+                PlanObject::Robot(RobotDetails {
+                    name: name.to_string(),
+                    desc: String::new(),
+                }),
+            ));
+        }
+
+        for name in &selection.teams {
+            actions.push(PlanAction::new(
+                Change::Delete,
+                PlanObject::Team(Team {
+                    name: name.to_string(),
+                    description: String::new(),
+                    members: Members {
+                        users: Vec::new(),
+                        robots: Vec::new(),
+                    },
+                    role: String::new(),
+                }),
+            ));
+        }
+
+        let robot_prefix = format!("{}+", organization.quay_organization);
+        for (team, member) in &selection.team_members {
+            let (name, is_robot) = match member.strip_prefix(&robot_prefix) {
+                Some(robot) => (robot.to_string(), true),
+                None => (
+                    member.to_string(),
+                    organization.robots.iter().any(|r| &r.name == member),
+                ),
+            };
+            actions.push(PlanAction::new(
+                Change::Delete,
+                PlanObject::TeamMember {
+                    team: team.to_string(),
+                    name,
+                    is_robot,
+                },
+            ));
+        }
+
+        for name in &selection.repositories {
+            actions.push(PlanAction::new(
+                Change::Delete,
+                PlanObject::Repository(Repository {
+                    name: name.to_string(),
+                    description: None,
+                    visibility: None,
+                    mirror: false,
+                    mirror_params: None,
+                    permissions: None,
+                }),
+            ));
+        }
+
+        OrganizationPlan {
+            organization: organization.clone(),
+            actions,
+            existing_unchanged: false,
+        }
+    }
+}
+
+impl OrganizationYaml {
+    /// Computes the plan deleting the objects not declared in the yaml configuration,
+    /// as allowed by quay_prune settings.
+    pub async fn prune_plan(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<OrganizationPlan, Box<dyn Error>> {
+        let live = self.get_live_state(quay_fn_arguments).await?;
+
+        let mut plan = OrganizationPlan::new(self, live.as_ref());
+        plan.actions.retain(|a| a.change == Change::Delete);
+        plan.retain_prunable();
+
+        Ok(plan)
+    }
+}
@@ -0,0 +1,206 @@
+use super::organization_struct::{Actions, OrganizationYaml, QuayFnArguments};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+
+/// Operations a command runs on the organizations, defining the permissions the token needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operations {
+    /// Plan and drift only read the organizations
+    Read,
+    /// Create and apply create, update and prune every object of the yaml files
+    Reconcile,
+    Delete,
+}
+
+/// Permissions of the token on an organization, as reported by Quay for the token user.
+#[derive(Debug, Clone, Copy)]
+pub struct OrganizationAccess {
+    pub is_admin: bool,
+    pub can_create_repo: bool,
+}
+
+/// Answer of Quay about the token of an endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct TokenCheck {
+    /// User owning the token, `None` if the token can not read it (user:read scope missing)
+    pub username: Option<String>,
+    /// Organizations of the token user
+    pub organizations: HashMap<String, OrganizationAccess>,
+    /// Set if the token is rejected or the endpoint is unreachable
+    pub error: Option<String>,
+}
+
+/// Result of the preflight checks of an organization.
+#[derive(Debug, Clone)]
+pub struct OrganizationPreflight {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+    pub username: Option<String>,
+    /// False if the organization does not exist yet
+    pub exists: bool,
+    /// True if the token can administer the organization, `None` if unknown
+    pub is_admin: Option<bool>,
+    /// OAuth scopes, or permissions, missing for the operations required by the yaml file
+    pub missing: Vec<&'static str>,
+    /// The token is rejected, or the organization can not be read
+    pub errors: Vec<String>,
+}
+
+impl OrganizationPreflight {
+    fn new(organization: &OrganizationYaml, token: &TokenCheck) -> OrganizationPreflight {
+        OrganizationPreflight {
+            quay_endpoint: organization.quay_endpoint.clone(),
+            quay_organization: organization.quay_organization.clone(),
+            username: token.username.clone(),
+            exists: false,
+            is_admin: None,
+            missing: Vec::new(),
+            errors: token.error.iter().cloned().collect(),
+        }
+    }
+
+    /// Returns true if the operations can run on the organization.
+    pub fn is_ok(&self, operations: Operations) -> bool {
+        self.errors.is_empty() && (operations == Operations::Read || self.missing.is_empty())
+    }
+}
+
+/// Describes a token rejected by Quay.
+fn rejected(status_code: StatusCode, message: Option<&str>) -> String {
+    format!(
+        "Token rejected by Quay with status code {}: {}",
+        status_code,
+        message.unwrap_or_default()
+    )
+}
+
+impl OrganizationYaml {
+    /// Checks the token of the endpoint reading its user, with the organizations it belongs to.
+    pub async fn check_token(&self, quay_fn_arguments: QuayFnArguments) -> TokenCheck {
+        let response = match self.get_user(quay_fn_arguments).await {
+            Ok(response) => response,
+            Err(e) => {
+                return TokenCheck {
+                    error: Some(format!("Unable to reach {}: {}", self.quay_endpoint, e)),
+                    ..Default::default()
+                }
+            }
+        };
+
+        match response.status_code {
+            s if s.is_success() => TokenCheck {
+                username: response.response["username"].as_str().map(str::to_string),
+                organizations: response.response["organizations"]
+                    .as_array()
+                    .unwrap_or(&Vec::new())
+                    .iter()
+                    .filter_map(|org| {
+                        Some((
+                            org["name"].as_str()?.to_string(),
+                            OrganizationAccess {
+                                is_admin: org["is_org_admin"].as_bool().unwrap_or_default(),
+                                can_create_repo: org["can_create_repo"]
+                                    .as_bool()
+                                    .unwrap_or_default(),
+                            },
+                        ))
+                    })
+                    .collect(),
+                error: None,
+            },
+            // A valid token without the user:read scope
+            StatusCode::FORBIDDEN => TokenCheck::default(),
+            s => TokenCheck {
+                error: Some(rejected(s, response.error_message())),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Checks that the token can run the operations on the organization, sending only read requests.
+    ///
+    /// Quay does not expose the scopes of a token: they are inferred from the permissions Quay
+    /// reports for the organization, and from the answer to reading the mirror configurations.
+    pub async fn preflight(
+        &self,
+        token: &TokenCheck,
+        operations: Operations,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> OrganizationPreflight {
+        let mut preflight = OrganizationPreflight::new(self, token);
+        if !preflight.errors.is_empty() {
+            return preflight;
+        }
+        let access = token.organizations.get(&self.quay_organization);
+
+        let response = match self.get_organization(quay_fn_arguments.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                preflight
+                    .errors
+                    .push(format!("Unable to read the organization: {}", e));
+                return preflight;
+            }
+        };
+        match response.status_code {
+            // Created by create and apply, nothing to delete
+            StatusCode::NOT_FOUND => return preflight,
+            StatusCode::UNAUTHORIZED => {
+                preflight
+                    .errors
+                    .push(rejected(response.status_code, response.error_message()));
+                return preflight;
+            }
+            StatusCode::FORBIDDEN => {
+                preflight.exists = true;
+                preflight.is_admin = Some(false);
+            }
+            s if s.is_success() => {
+                preflight.exists = true;
+                preflight.is_admin = response.response["is_admin"]
+                    .as_bool()
+                    .or(access.map(|a| a.is_admin));
+            }
+            s => {
+                preflight.errors.push(format!(
+                    "Unable to read the organization: status code {}",
+                    s
+                ));
+                return preflight;
+            }
+        }
+
+        if preflight.is_admin == Some(false) {
+            preflight.missing.push("org:admin");
+        }
+        if operations == Operations::Delete {
+            return preflight;
+        }
+
+        if !self.repositories.is_empty() && access.is_some_and(|a| !a.can_create_repo) {
+            preflight.missing.push("repo:create");
+        }
+
+        for repo in self.repositories.iter().filter(|r| r.mirror) {
+            match self
+                .get_repository_mirror(&repo.name, quay_fn_arguments.clone())
+                .await
+            {
+                Ok(response) if response.status_code == StatusCode::FORBIDDEN => {
+                    preflight.missing.push("repo:admin");
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    preflight.errors.push(format!(
+                        "Unable to read the mirror configuration of '{}': {}",
+                        repo.name, e
+                    ));
+                    break;
+                }
+            }
+        }
+
+        preflight
+    }
+}
@@ -0,0 +1,393 @@
+use super::organization_plan::{Change, ObjectKey, OrganizationPlan, PlanAction, PlanObject};
+use super::organization_struct::{OrganizationYaml, QuayFnArguments, QuayResponse};
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{error, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Outcome of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    Succeeded,
+    /// Not executed by Quay because the object already exists.
+    Unchanged,
+    Failed,
+    /// Not executed because a task it depends on failed or was skipped.
+    Skipped,
+}
+
+impl TaskStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskStatus::Succeeded => "succeeded",
+            TaskStatus::Unchanged => "unchanged",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// Result of a plan action executed as a task.
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    pub action: PlanAction,
+    pub status: TaskStatus,
+    /// HTTP method of the last request sent by the task, `None` if no response was received
+    pub method: Option<String>,
+    /// Status code of the last response, `None` if no response was received
+    pub status_code: Option<u16>,
+    /// Execution time of the task
+    pub duration: Duration,
+    /// Description of the last request, `None` if no response was received
+    pub description: Option<String>,
+    /// Status of the last response with the error returned by Quay, or why the task failed or was skipped
+    pub message: String,
+}
+
+impl TaskResult {
+    fn skipped(action: &PlanAction, message: String) -> TaskResult {
+        TaskResult {
+            action: action.clone(),
+            status: TaskStatus::Skipped,
+            method: None,
+            status_code: None,
+            duration: Duration::ZERO,
+            description: None,
+            message,
+        }
+    }
+}
+
+/// Results of the tasks executed for an organization.
+#[derive(Debug, Clone)]
+pub struct OrganizationRun {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+    pub tasks: Vec<TaskResult>,
+    /// Errors not related to a single task, e.g. the live state can not be read.
+    pub errors: Vec<String>,
+}
+
+impl OrganizationRun {
+    fn new(organization: &OrganizationYaml) -> OrganizationRun {
+        OrganizationRun {
+            quay_endpoint: organization.quay_endpoint.clone(),
+            quay_organization: organization.quay_organization.clone(),
+            tasks: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Run of an organization not executed because of errors.
+    pub fn failed(organization: &OrganizationYaml, errors: Vec<String>) -> OrganizationRun {
+        OrganizationRun {
+            errors,
+            ..OrganizationRun::new(organization)
+        }
+    }
+
+    pub fn count(&self, status: TaskStatus) -> usize {
+        self.tasks.iter().filter(|t| t.status == status).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.errors.is_empty() || self.count(TaskStatus::Failed) > 0
+    }
+}
+
+impl OrganizationPlan {
+    /// For every action, the indexes of the actions it depends on.
+    ///
+    /// Creations and updates wait for the creation of the objects they require,
+    /// deletions wait for the deletion of the objects requiring them.
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        let mut created: HashMap<&ObjectKey, usize> = HashMap::new();
+        let mut deleted_requirements: HashMap<ObjectKey, Vec<usize>> = HashMap::new();
+        let keys: Vec<Option<ObjectKey>> = self.actions.iter().map(|a| a.object.key()).collect();
+
+        for (i, action) in self.actions.iter().enumerate() {
+            if action.change == Change::Delete {
+                for requirement in action.object.requirements() {
+                    deleted_requirements.entry(requirement).or_default().push(i);
+                }
+            } else if let Some(key) = &keys[i] {
+                created.insert(key, i);
+            }
+        }
+
+        self.actions
+            .iter()
+            .enumerate()
+            .map(|(i, action)| {
+                if action.change == Change::Delete {
+                    keys[i]
+                        .as_ref()
+                        .and_then(|key| deleted_requirements.get(key))
+                        .cloned()
+                        .unwrap_or_default()
+                } else {
+                    action
+                        .object
+                        .requirements()
+                        .iter()
+                        .filter_map(|requirement| created.get(requirement).copied())
+                        .filter(|&j| j != i)
+                        .collect()
+                }
+            })
+            .collect()
+    }
+
+    /// Executes the plan as a graph of tasks.
+    ///
+    /// A task starts as soon as the tasks it depends on succeeded, at most
+    /// `concurrency` tasks run at the same time. When a task fails, the tasks depending on it are skipped.
+    pub async fn execute(&self, quay_fn_arguments: QuayFnArguments) -> OrganizationRun {
+        let dependencies = self.dependencies();
+        let mut dependents = vec![Vec::new(); self.actions.len()];
+        for (i, deps) in dependencies.iter().enumerate() {
+            for &j in deps {
+                dependents[j].push(i);
+            }
+        }
+        let mut pending: Vec<usize> = dependencies.iter().map(|d| d.len()).collect();
+        let mut results: Vec<Option<TaskResult>> = vec![None; self.actions.len()];
+        let progress = quay_fn_arguments.progress.as_ref();
+        let task_done = || {
+            if let Some(progress) = progress {
+                progress.task_done(
+                    &self.organization.quay_endpoint,
+                    &self.organization.quay_organization,
+                );
+            }
+        };
+        if let Some(progress) = progress {
+            progress.add_tasks(
+                &self.organization.quay_endpoint,
+                &self.organization.quay_organization,
+                self.actions.len(),
+            );
+        }
+        let aborted = || {
+            quay_fn_arguments
+                .fail_fast
+                .as_ref()
+                .is_some_and(|failed| failed.load(Ordering::Relaxed))
+        };
+
+        let start = |i: usize| {
+            let action = &self.actions[i];
+            let quay_fn_arguments = quay_fn_arguments.clone();
+            async move {
+                let now = Instant::now();
+                let mut result = action
+                    .execute(&self.organization, quay_fn_arguments.clone())
+                    .await;
+                // An existing repository is updated, so its description and visibility are set too.
+                if self.existing_unchanged
+                    && action.change == Change::Create
+                    && matches!(action.object, PlanObject::Repository(_))
+                    && result.as_ref().is_ok_and(|r| r.already_exists())
+                {
+                    let update = PlanAction {
+                        change: Change::Update,
+                        ..action.clone()
+                    };
+                    result = update.execute(&self.organization, quay_fn_arguments).await;
+                }
+                (i, result, now.elapsed())
+            }
+        };
+
+        // Tasks are started only while less than `concurrency` tasks are running.
+        let mut ready: VecDeque<usize> = (0..self.actions.len())
+            .filter(|&i| pending[i] == 0)
+            .collect();
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            while running.len() < quay_fn_arguments.concurrency.max(1) && !aborted() {
+                match ready.pop_front() {
+                    Some(i) => running.push(start(i)),
+                    None => break,
+                }
+            }
+
+            let (i, result, duration) = match running.next().await {
+                Some(next) => next,
+                None => break,
+            };
+
+            let task = self.task_outcome(&self.actions[i], result, duration, &quay_fn_arguments);
+            let status = task.status;
+            results[i] = Some(task);
+            task_done();
+
+            if matches!(status, TaskStatus::Succeeded | TaskStatus::Unchanged) {
+                for &d in &dependents[i] {
+                    pending[d] -= 1;
+                    if pending[d] == 0 && results[d].is_none() {
+                        ready.push_back(d);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(failed) = &quay_fn_arguments.fail_fast {
+                failed.store(true, Ordering::Relaxed);
+            }
+
+            // Skip everything depending on the failed task
+            let mut to_skip = dependents[i].clone();
+            while let Some(d) = to_skip.pop() {
+                if results[d].is_some() {
+                    continue;
+                }
+                warn!(
+                    "Organization '{}' on '{}': skipping {} because {} failed",
+                    self.organization.quay_organization,
+                    self.organization.quay_endpoint,
+                    self.actions[d].object,
+                    self.actions[i].object
+                );
+                results[d] = Some(TaskResult::skipped(
+                    &self.actions[d],
+                    format!("Skipped because '{}' failed", self.actions[i].object),
+                ));
+                task_done();
+                to_skip.extend(&dependents[d]);
+            }
+        }
+
+        let mut run = OrganizationRun::new(&self.organization);
+        let reason = if aborted() {
+            "Skipped because of --fail-fast"
+        } else {
+            "Skipped because of a dependency cycle"
+        };
+        for (action, result) in self.actions.iter().zip(results) {
+            run.tasks.push(result.unwrap_or_else(|| {
+                task_done();
+                TaskResult::skipped(action, reason.to_string())
+            }));
+        }
+        run
+    }
+
+    fn task_outcome(
+        &self,
+        action: &PlanAction,
+        result: Result<QuayResponse, Box<dyn Error>>,
+        duration: Duration,
+        quay_fn_arguments: &QuayFnArguments,
+    ) -> TaskResult {
+        let response = result.as_ref().ok();
+        let method = response.map(|r| r.method.to_string());
+        let status_code = response.map(|r| r.status_code.as_u16());
+        let description = response.map(|r| r.description.clone());
+        let quay_status = |r: &QuayResponse| match r.error_message() {
+            Some(error) => format!("{}: {}", r.status_code, error),
+            None => r.status_code.to_string(),
+        };
+
+        let (status, message) = match result {
+            Ok(r) if r.is_success() => {
+                if quay_fn_arguments.log_verbosity >= 5 {
+                    info!("{:?}", r);
+                }
+                (TaskStatus::Succeeded, quay_status(&r))
+            }
+            // Only expected when every object is created again on purpose, or when a retried
+            // request was already executed by a previous attempt.
+            Ok(r) if r.already_exists() && (self.existing_unchanged || r.attempts > 1) => {
+                info!(
+                    "Organization '{}' on '{}': {} unchanged, it already exists",
+                    self.organization.quay_organization,
+                    self.organization.quay_endpoint,
+                    action.object
+                );
+                (TaskStatus::Unchanged, quay_status(&r))
+            }
+            Ok(r) => {
+                let message = quay_status(&r);
+                error!(
+                    "Organization '{}' on '{}': {} -> {} failed with status code {}",
+                    self.organization.quay_organization,
+                    self.organization.quay_endpoint,
+                    action.object,
+                    r.description,
+                    message
+                );
+                (TaskStatus::Failed, message)
+            }
+            Err(e) => {
+                error!(
+                    "Organization '{}' on '{}': {} -> {}",
+                    self.organization.quay_organization,
+                    self.organization.quay_endpoint,
+                    action.object,
+                    e
+                );
+                (TaskStatus::Failed, e.to_string())
+            }
+        };
+
+        TaskResult {
+            action: action.clone(),
+            status,
+            method,
+            status_code,
+            duration,
+            description,
+            message,
+        }
+    }
+}
+
+impl OrganizationYaml {
+    /// Creates every object of the organization, then prunes the objects not declared in the
+    /// yaml configuration. Pruning is skipped if any creation failed.
+    pub async fn create_and_prune(&self, quay_fn_arguments: QuayFnArguments) -> OrganizationRun {
+        let plan = OrganizationPlan {
+            existing_unchanged: true,
+            ..OrganizationPlan::new(self, None)
+        };
+        let mut run = plan.execute(quay_fn_arguments.clone()).await;
+
+        if !self.is_pruning_enabled() {
+            return run;
+        }
+
+        if run.has_failures()
+            || quay_fn_arguments
+                .fail_fast
+                .as_ref()
+                .is_some_and(|failed| failed.load(Ordering::Relaxed))
+        {
+            warn!(
+                "Organization '{}' on '{}': pruning skipped because of failed tasks",
+                self.quay_organization, self.quay_endpoint
+            );
+            return run;
+        }
+
+        match self.prune_plan(quay_fn_arguments.clone()).await {
+            Ok(plan) => {
+                let prune_run = plan.execute(quay_fn_arguments).await;
+                run.tasks.extend(prune_run.tasks);
+            }
+            Err(e) => {
+                error!(
+                    "Organization '{}' on '{}': unable to prune: {}",
+                    self.quay_organization, self.quay_endpoint, e
+                );
+                run.errors.push(format!("Unable to prune: {}", e));
+            }
+        }
+
+        run
+    }
+}
@@ -0,0 +1,135 @@
+use super::organization_struct::OrganizationYaml;
+use super::organization_validation::{REPOSITORY_ROLES, TEAM_ROLES, VISIBILITIES};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, Metadata, ObjectValidation, RootSchema, Schema, SchemaObject, StringValidation,
+    SubschemaValidation,
+};
+use schemars::schema_for;
+use serde_ignored::Path;
+use std::error::Error;
+use std::fmt::Display;
+
+/// Top level block of the template variables, not deserialized.
+const VARS: &str = "vars";
+
+/// Strings with a `{{ }}` expression, rendered before the file is deserialized.
+const TEMPLATE_PATTERN: &str = r"\{\{.*\}\}";
+
+/// JSON Schema of the organization yaml files, for the editors.
+///
+/// Unknown fields are rejected, so misspelled keys are flagged while editing.
+/// Roles and visibility accept their values, or a template expression.
+pub fn organization_schema() -> RootSchema {
+    let mut schema = schema_for!(OrganizationYaml);
+
+    let scalar = SchemaObject {
+        instance_type: Some(
+            vec![
+                InstanceType::String,
+                InstanceType::Number,
+                InstanceType::Boolean,
+            ]
+            .into(),
+        ),
+        ..Default::default()
+    };
+    let vars = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(
+                "Template variables, used as {{ name }} and overridden by --var".to_string(),
+            ),
+            ..Default::default()
+        })),
+        object: Some(Box::new(ObjectValidation {
+            additional_properties: Some(Box::new(scalar.into())),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    schema
+        .schema
+        .object()
+        .properties
+        .insert(VARS.to_string(), vars.into());
+
+    schema
+}
+
+/// Deserializes an organization yaml file, failing on the fields missing from its schema,
+/// which serde would silently ignore. The vars block of the templates is accepted.
+pub fn from_str_strict(content: &str) -> Result<OrganizationYaml, Box<dyn Error>> {
+    let mut unknown = Vec::new();
+    let organization: OrganizationYaml =
+        serde_ignored::deserialize(serde_yaml::Deserializer::from_str(content), |path| {
+            let path = field_path(&path);
+            if path != VARS {
+                unknown.push(format!("'{}'", path));
+            }
+        })?;
+
+    if !unknown.is_empty() {
+        return Err(format!("unknown field {}", unknown.join(", ")).into());
+    }
+    Ok(organization)
+}
+
+/// Dotted path of a field, as repositories.0.visibility, without the optional values.
+fn field_path(path: &Path<'_>) -> String {
+    let join = |parent: &Path<'_>, child: &dyn Display| match field_path(parent) {
+        parent if parent.is_empty() => child.to_string(),
+        parent => format!("{}.{}", parent, child),
+    };
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => join(parent, index),
+        Path::Map { parent, key } => join(parent, key),
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+pub fn visibility(_: &mut SchemaGenerator) -> Schema {
+    one_of(&VISIBILITIES)
+}
+
+pub fn repository_role(_: &mut SchemaGenerator) -> Schema {
+    one_of(&REPOSITORY_ROLES)
+}
+
+pub fn team_role(_: &mut SchemaGenerator) -> Schema {
+    one_of(&TEAM_ROLES)
+}
+
+/// String with one of the values, or a template expression.
+fn one_of(values: &[&str]) -> Schema {
+    let string = |object: SchemaObject| -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            ..object
+        }
+        .into()
+    };
+    let allowed = string(SchemaObject {
+        enum_values: Some(values.iter().map(|&value| value.into()).collect()),
+        ..Default::default()
+    });
+    let template = string(SchemaObject {
+        string: Some(Box::new(StringValidation {
+            pattern: Some(TEMPLATE_PATTERN.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    });
+
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![allowed, template]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
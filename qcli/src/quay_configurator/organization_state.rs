@@ -0,0 +1,254 @@
+use super::organization_struct::{
+    Actions, Members, MirrorConfig, MirrorParams, OrganizationYaml, Permissions, QuayFnArguments,
+    QuayResponse, Repository, RobotDetails, Team, UserElement,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::error::Error;
+
+/// Team created by Quay for every organization, it is managed by Quay itself.
+pub const OWNERS_TEAM: &str = "owners";
+
+impl OrganizationYaml {
+    /// Reads the live state of the organization from Quay.
+    ///
+    /// The live state is returned with the same shape of the yaml configuration, so it can be
+    /// compared with the wanted one. Returns `None` if the organization does not exist.
+    pub async fn get_live_state(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<Option<OrganizationYaml>, Box<dyn Error>> {
+        let response = self.get_organization(quay_fn_arguments.clone()).await?;
+        if response.status_code == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let organization = expect_success(response)?;
+
+        let mut live = self.clone();
+        live.replicate_to = None;
+        live.quay_organization_role_email = organization["email"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        // Robots
+        let robots = expect_success(
+            self.get_organization_robots(quay_fn_arguments.clone())
+                .await?,
+        )?;
+        live.robots = robots["robots"]
+            .as_array()
+            .unwrap_or(&Vec::new())
+            .iter()
+            .filter_map(|robot| {
+                Some(RobotDetails {
+                    name: self.robot_short_name(robot["name"].as_str()?),
+                    desc: robot["description"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+            })
+            .collect();
+
+        // Teams and team members
+        live.teams = Vec::new();
+        if let Some(teams) = organization["teams"].as_object() {
+            for (name, team) in teams {
+                let members = expect_success(
+                    self.get_team_members(name, quay_fn_arguments.clone())
+                        .await?,
+                )?;
+
+                let mut live_members = Members {
+                    users: Vec::new(),
+                    robots: Vec::new(),
+                };
+                for member in members["members"].as_array().unwrap_or(&Vec::new()) {
+                    if let Some(member_name) = member["name"].as_str() {
+                        if member["is_robot"].as_bool().unwrap_or_default() {
+                            live_members.robots.push(self.robot_short_name(member_name));
+                        } else {
+                            live_members.users.push(member_name.to_string());
+                        }
+                    }
+                }
+
+                live.teams.push(Team {
+                    name: name.to_string(),
+                    description: team["description"].as_str().unwrap_or_default().to_string(),
+                    members: live_members,
+                    role: team["role"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+        }
+
+        // Repositories, permissions and mirror configurations
+        let repositories = expect_success(
+            self.get_organization_repositories(quay_fn_arguments.clone())
+                .await?,
+        )?;
+        live.repositories = Vec::new();
+        for repo in repositories["repositories"]
+            .as_array()
+            .unwrap_or(&Vec::new())
+        {
+            let name = match repo["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+
+            let permissions = self
+                .get_live_permissions(&name, quay_fn_arguments.clone())
+                .await?;
+
+            // Older Quay versions do not return the repository state, in that case the mirror configuration is always read.
+            let mirror_params = match repo["state"].as_str() {
+                Some(state) if state != "MIRROR" => None,
+                _ => {
+                    self.get_live_mirror(&name, quay_fn_arguments.clone())
+                        .await?
+                }
+            };
+
+            let description = repo["description"].as_str().unwrap_or_default();
+            let visibility = match repo["is_public"].as_bool() {
+                Some(false) => "private",
+                _ => "public",
+            };
+
+            live.repositories.push(Repository {
+                name,
+                description: (!description.is_empty()).then(|| description.to_string()),
+                visibility: Some(visibility.to_string()),
+                mirror: mirror_params.is_some(),
+                mirror_params,
+                permissions,
+            });
+        }
+
+        Ok(Some(live))
+    }
+
+    async fn get_live_permissions(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<Option<Permissions>, Box<dyn Error>> {
+        let mut permissions = Permissions::new();
+
+        // Users and robots
+        let users = expect_success(
+            self.get_repository_user_permissions(repo, quay_fn_arguments.clone())
+                .await?,
+        )?;
+        if let Some(objs_permissions) = users["permissions"].as_object() {
+            for v in objs_permissions.values() {
+                if let (Some(name), Some(role)) = (v["name"].as_str(), v["role"].as_str()) {
+                    if v["is_robot"].as_bool().unwrap_or_default() {
+                        permissions.robots.push(UserElement::new(
+                            self.robot_short_name(name),
+                            role.to_string(),
+                        ));
+                    } else {
+                        permissions
+                            .users
+                            .push(UserElement::new(name.to_string(), role.to_string()));
+                    }
+                }
+            }
+        }
+
+        // Teams
+        let teams = expect_success(
+            self.get_repository_team_permissions(repo, quay_fn_arguments)
+                .await?,
+        )?;
+        let mut team_permissions = Vec::new();
+        if let Some(objs_permissions) = teams["permissions"].as_object() {
+            for v in objs_permissions.values() {
+                if let (Some(name), Some(role)) = (v["name"].as_str(), v["role"].as_str()) {
+                    team_permissions.push(UserElement::new(name.to_string(), role.to_string()));
+                }
+            }
+        }
+
+        if permissions.users.is_empty()
+            && permissions.robots.is_empty()
+            && team_permissions.is_empty()
+        {
+            return Ok(None);
+        }
+        permissions.teams = (!team_permissions.is_empty()).then_some(team_permissions);
+
+        Ok(Some(permissions))
+    }
+
+    async fn get_live_mirror(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<Option<MirrorParams>, Box<dyn Error>> {
+        let response = self.get_repository_mirror(repo, quay_fn_arguments).await?;
+        if response.status_code == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let mirror = expect_success(response)?;
+        let mirror_config: MirrorConfig = serde_json::from_value(mirror)?;
+
+        let (src_registry, src_image) = mirror_config
+            .external_reference
+            .split_once('/')
+            .unwrap_or((mirror_config.external_reference.as_str(), ""));
+
+        Ok(Some(MirrorParams {
+            src_registry: src_registry.to_string(),
+            src_image: src_image.to_string(),
+            src_image_tags: mirror_config.root_rule.rule_value.clone(),
+            ext_registry_verify_tls: mirror_config.external_registry_config.verify_tls,
+            ext_registry_unsigned_image: Some(
+                mirror_config.external_registry_config.unsigned_images,
+            ),
+            robot_username: self.robot_short_name(&mirror_config.robot_username),
+            sync_interval: mirror_config.sync_interval,
+            is_enabled: mirror_config.is_enabled,
+            https_proxy: mirror_config
+                .external_registry_config
+                .proxy
+                .https_proxy
+                .clone(),
+            http_proxy: mirror_config
+                .external_registry_config
+                .proxy
+                .http_proxy
+                .clone(),
+            no_proxy: mirror_config
+                .external_registry_config
+                .proxy
+                .no_proxy
+                .clone(),
+            ext_registry_username: mirror_config.external_registry_username.clone(),
+            ext_registry_password: None,
+        }))
+    }
+
+    /// Quay returns robot names as '<organization>+<robot>', the yaml files use only '<robot>'.
+    fn robot_short_name(&self, name: &str) -> String {
+        name.strip_prefix(&format!("{}+", self.quay_organization))
+            .unwrap_or(name)
+            .to_string()
+    }
+}
+
+/// Returns the response body if Quay answered with a success status code.
+fn expect_success(response: QuayResponse) -> Result<Value, Box<dyn Error>> {
+    if response.status_code.is_success() {
+        Ok(response.response)
+    } else {
+        Err(format!(
+            "{} failed with status code {}: {}",
+            response.description, response.status_code, response.response
+        )
+        .into())
+    }
+}
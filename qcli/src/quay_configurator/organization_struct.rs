@@ -1,26 +1,88 @@
-
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use governor::clock::{QuantaClock, QuantaInstant};
 use governor::middleware::NoOpMiddleware;
 use governor::state::{InMemoryState, NotKeyed};
 use governor::{self, RateLimiter};
-use log::debug;
+use log::{debug, info, warn};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::{collections::HashMap, error::Error, time::Duration};
-use substring::Substring;
+use tokio::sync::Semaphore;
 
-use reqwest::{Method, StatusCode};
+use reqwest::{Method, StatusCode, Url};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::organization_schema;
 use super::quay_config_reader::MirrorLogin;
+use super::retry_policy::RetryPolicy;
+use super::run_progress::RunProgress;
 
 #[derive(Debug, Default, Clone)]
 pub struct QuayResponse {
     pub response: Value,
     pub status_code: StatusCode,
     pub description: String,
+    /// HTTP method of the request
+    pub method: Method,
+    /// Number of times the request was sent, more than one if it was retried
+    pub attempts: u32,
+}
+
+impl QuayResponse {
+    /// Error message returned by Quay, if any.
+    pub fn error_message(&self) -> Option<&str> {
+        ["error_message", "message", "detail"]
+            .iter()
+            .find_map(|key| self.response[key].as_str())
+    }
+
+    /// True if Quay executed the request.
+    pub fn is_success(&self) -> bool {
+        self.status_code.is_success()
+    }
+
+    /// True if Quay rejected the creation of an object because it already exists.
+    pub fn already_exists(&self) -> bool {
+        matches!(
+            self.status_code,
+            StatusCode::BAD_REQUEST | StatusCode::CONFLICT
+        ) && self.error_message().is_some_and(|message| {
+            message.contains("already exists") || message.starts_with("Existing")
+        })
+    }
+}
+
+/// Base URL of the Quay API: `<scheme>://<host>[:<port>][/<prefix>]/api/v1`.
+///
+/// The endpoint is a host name with an optional port, using https, or a full URL
+/// with scheme, port and path prefix, e.g. 'http://localhost:8080/quay'.
+pub fn api_base_url(quay_endpoint: &str) -> Result<Url, Box<dyn Error>> {
+    let endpoint = quay_endpoint.trim_end_matches('/');
+    let mut url = match endpoint.contains("://") {
+        true => Url::parse(endpoint),
+        false => Url::parse(&format!("https://{}", endpoint)),
+    }
+    .map_err(|e| format!("Invalid Quay endpoint '{}': {}", quay_endpoint, e))?;
+
+    if url.cannot_be_a_base() || url.host_str().is_none() {
+        return Err(format!("Invalid Quay endpoint '{}'", quay_endpoint).into());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!(
+            "Invalid Quay endpoint '{}': query and fragment are not allowed",
+            quay_endpoint
+        )
+        .into());
+    }
+
+    url.path_segments_mut()
+        .map_err(|_| format!("Invalid Quay endpoint '{}'", quay_endpoint))?
+        .pop_if_empty()
+        .extend(["api", "v1"]);
+    Ok(url)
 }
 
 #[async_trait]
@@ -31,47 +93,64 @@ pub trait Actions {
     ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn grant_user_permission_to_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn delete_user_permission_from_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
 
     async fn delete_team_permission_from_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
-    async fn get_user_permission_from_repository(
+    async fn grant_robot_permission_to_repository(
         &self,
-        repo: &Repository,
+        repo: &str,
+        user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
-    async fn get_team_permission_from_repository(
+    async fn grant_team_permission_to_repository(
         &self,
-        repo: &Repository,
+        repo: &str,
+        user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
-    async fn grant_robot_permission_to_repository(
+    async fn delete_organization(
         &self,
-        repo: &String,
-        user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
-    async fn grant_team_permission_to_repository(
+    async fn delete_robot(
         &self,
-        repo: &String,
-        user: &UserElement,
+        robot: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
-    async fn delete_organization(
+    async fn delete_team(
         &self,
+        team: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn delete_user_from_team(
+        &self,
+        team: &str,
+        user: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn delete_robot_from_team(
+        &self,
+        team: &str,
+        robot: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn delete_repository(
+        &self,
+        repo: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn create_robot(
@@ -86,15 +165,15 @@ pub trait Actions {
     ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn add_user_to_team(
         &self,
-        team: &String,
-        user: &String,
+        team: &str,
+        user: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
 
     async fn add_robot_to_team(
         &self,
-        team: &String,
-        user: &String,
+        team: &str,
+        user: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn create_repository(
@@ -107,53 +186,149 @@ pub trait Actions {
         team: &Repository,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn update_repository(
+        &self,
+        repo: &Repository,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_user(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_organization(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_organization_robots(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_team_members(
+        &self,
+        team: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_organization_repositories(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_repository_user_permissions(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_repository_team_permissions(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
+    async fn get_repository_mirror(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>>;
     async fn send_request<T>(
         &self,
-        endpoint: String,
+        endpoint: Url,
         body: &T,
-        description: &String,
+        description: &str,
         method: reqwest::Method,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>>
     where
         T: Serialize + std::marker::Sync,
     {
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(quay_fn_arguments.tls_verify)
-            .build()?;
-
-        let api = client
-            .request(method, endpoint)
-            .timeout(Duration::from_secs(quay_fn_arguments.timeout))
-            .header("Content-Type", "application/json")
-            .header("accept", "application/json")
-            .header(
-                "Authorization",
-                format!("Bearer {}", &quay_fn_arguments.token),
-            )
-            .json(body);
-
-        ////println!("{:?}", api);
-        let retry_jitter = governor::Jitter::new(Duration::ZERO, Duration::from_millis(1));
-        quay_fn_arguments
-            .governor
-            .until_ready_with_jitter(retry_jitter)
-            .await;
-
-        //println("{:?}",governor.)
-        let response_status = api.send().await?;
-        let status_code = response_status.status();
-        let response = match response_status.json::<serde_json::Value>().await {
-            Ok(r) => r,
-            Err(_) => Value::Null,
-        };
-        ////println!("{:?}", response);
-        let quay_response = QuayResponse {
-            response,
-            status_code,
-            description: description.clone(),
+        let client = &quay_fn_arguments.client;
+
+        if quay_fn_arguments.log_verbosity >= 10 {
+            info!("{} {}", method, endpoint);
+        }
+
+        let retry_policy = quay_fn_arguments.retry_policy;
+        let mut attempt = 1;
+        let quay_response = loop {
+            let api = client
+                .request(method.clone(), endpoint.clone())
+                .header("Content-Type", "application/json")
+                .header("accept", "application/json")
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", &quay_fn_arguments.token),
+                )
+                .json(body);
+
+            // The endpoint permit and the endpoint rate limiter come first, so a slow endpoint
+            // (e.g. a replica with a lower quota) does not hold global permits.
+            let endpoint_permit = quay_fn_arguments.endpoint_limit.acquire().await?;
+
+            let retry_jitter = governor::Jitter::new(Duration::ZERO, Duration::from_millis(1));
+            quay_fn_arguments
+                .governor
+                .until_ready_with_jitter(retry_jitter)
+                .await;
+            if let Some(sent_requests) = &quay_fn_arguments.sent_requests {
+                sent_requests.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let global_permit = quay_fn_arguments.global_limit.acquire().await?;
+
+            // The last response is returned as is, with the error sent by Quay
+            let last_attempt = attempt >= retry_policy.max_attempts;
+            let (reason, retry_after) = match api.send().await {
+                Ok(response_status)
+                    if RetryPolicy::is_retryable_status(response_status.status())
+                        && !last_attempt =>
+                {
+                    (
+                        format!("status code {}", response_status.status()),
+                        RetryPolicy::retry_after(response_status.headers()),
+                    )
+                }
+                Ok(response_status) => {
+                    let status_code = response_status.status();
+                    let response = match response_status.json::<serde_json::Value>().await {
+                        Ok(r) => r,
+                        Err(_) => Value::Null,
+                    };
+                    break QuayResponse {
+                        response,
+                        status_code,
+                        description: description.to_string(),
+                        method: method.clone(),
+                        attempts: attempt,
+                    };
+                }
+                Err(e) if RetryPolicy::is_retryable_error(&e) && !last_attempt => {
+                    (e.to_string(), None)
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            drop(global_permit);
+            drop(endpoint_permit);
+
+            let delay = retry_policy.delay(attempt, retry_after);
+            warn!(
+                "{} {} failed with {}, retrying in {:.1} seconds (attempt {}/{})",
+                method,
+                endpoint,
+                reason,
+                delay.as_secs_f32(),
+                attempt + 1,
+                retry_policy.max_attempts
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         };
 
+        // A non idempotent request may have been executed by a previous attempt.
+        if attempt > 1 && quay_response.already_exists() {
+            info!(
+                "{}: already executed by a previous attempt, reported as unchanged",
+                description
+            );
+        }
+
         if quay_fn_arguments.log_level == log::Level::Debug {
             debug!("{:?}", quay_response.clone());
         }
@@ -167,7 +342,7 @@ impl Actions for OrganizationYaml {
         &self,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!("https://{}/api/v1/organization/", &self.quay_endpoint);
+        let endpoint = self.api_url(&["organization", ""])?;
         let mut body = HashMap::new();
         body.insert("name", &self.quay_organization);
         body.insert("email", &self.quay_organization_role_email);
@@ -190,14 +365,18 @@ impl Actions for OrganizationYaml {
 
     async fn grant_user_permission_to_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/user/{}",
-            &self.quay_endpoint, &self.quay_organization, repo, user.name
-        );
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "user",
+            &user.name,
+        ])?;
         let mut body = HashMap::new();
         body.insert("role", &user.role);
 
@@ -215,14 +394,18 @@ impl Actions for OrganizationYaml {
     }
     async fn delete_user_permission_from_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/user/{}",
-            &self.quay_endpoint, &self.quay_organization, repo, user.name
-        );
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "user",
+            &user.name,
+        ])?;
         let mut body: HashMap<&str, &String> = HashMap::new();
         body.insert("role", &user.role);
 
@@ -240,14 +423,18 @@ impl Actions for OrganizationYaml {
     }
     async fn delete_team_permission_from_repository(
         &self,
-        repo: &String,
+        repo: &str,
         user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/team/{}",
-            &self.quay_endpoint, &self.quay_organization, repo, user.name
-        );
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "team",
+            &user.name,
+        ])?;
         let mut body: HashMap<&str, &String> = HashMap::new();
         body.insert("role", &user.role);
 
@@ -264,321 +451,235 @@ impl Actions for OrganizationYaml {
         Ok(response.clone())
     }
 
-    async fn get_user_permission_from_repository(
+    async fn grant_robot_permission_to_repository(
         &self,
-        repo: &Repository,
+        repo: &str,
+        user: &UserElement,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/user/",
-            &self.quay_endpoint, &self.quay_organization, repo.name,
-        );
-        let body: HashMap<&str, &String> = HashMap::new();
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "user",
+            &format!("{}+{}", &self.quay_organization, user.name),
+        ])?;
+        let mut body = HashMap::new();
+        body.insert("role", &user.role);
+
         let response = &self
             .send_request(
                 endpoint,
                 &body,
                 &self.quay_organization,
-                Method::GET,
-                quay_fn_arguments.clone(),
+                Method::PUT,
+                quay_fn_arguments,
             )
             .await?;
 
-        ////println!("{} - {}", &self.quay_organization, repo.name);
-        match response.status_code {
-            StatusCode::OK => {
-                let mut actual_repo_permissions: Permissions = Permissions::new();
-                //println!();
-                //println!("####################");
-                //println!(
-                //   "Organization {} :: Repository: {} ",
-                //    &self.quay_organization, repo.name
-                //);
-                //println!("####################");
-                //println!();
-                //For users and robots
-                if let Some(objs) = response.response.as_object() {
-                    if let Some(objs_permissions) = objs["permissions"].as_object() {
-                        for (_, v) in objs_permissions.iter() {
-                            if let Some(name) = v["name"].as_str() {
-                                if let Some(role) = v["role"].as_str() {
-                                    if let Some(is_robot) = v["is_robot"].as_bool() {
-                                        if is_robot {
-                                            let single_robot_permission = UserElement::new(
-                                                name.to_string()
-                                                    .substring(
-                                                        self.quay_organization.len() + 1,
-                                                        name.len(),
-                                                    )
-                                                    .to_string(),
-                                                role.to_string(),
-                                            );
-                                            actual_repo_permissions
-                                                .robots
-                                                .push(single_robot_permission);
-                                        } else {
-                                            let single_user_permission = UserElement::new(
-                                                name.to_string(),
-                                                role.to_string(),
-                                            );
-                                            actual_repo_permissions
-                                                .users
-                                                .push(single_user_permission);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
-                ////println!("Actual permissions: {:?}", actual_repo_permissions);
-                //println!("---------");
-                let configured_repository = self.repositories.iter().find(|r| r == &repo);
-                ////println!("{:?}",configured_repository);
-                match configured_repository {
-                    Some(configured_repo) => {
-                        let mut diff_users: Vec<UserElement> = actual_repo_permissions.users;
-                        //println!("Actual USERS permissions {:?}", diff_users);
-
-                        if let Some(user) = configured_repo.permissions.as_ref() {
-                            for el_permission in &user.users {
-                                diff_users.retain(|x| x != el_permission);
-                            }
-                            //println!("Wanted USERS permissions {:?}", &user.users);
-                        } else {
-                            //println!("Wanted USERS permissions: NONE");
-                            //If there is not a wanted user, Quay adds a single admin user so the difference must be zero.
-                            if diff_users.len() > 0 {
-                                diff_users.clear();
-                                //println!("--> The admin user is not being counted.")
-                            }
-                        }
-
-                        //println!("Difference USER permissions {:?}", diff_users);
-
-                        for user in diff_users {
-                            self.delete_user_permission_from_repository(
-                                &repo.name,
-                                &user,
-                                quay_fn_arguments.clone(),
-                            )
-                            .await?;
-                        }
-
-                        //println!();
-
-                        let mut diff_robots: Vec<UserElement> = actual_repo_permissions.robots;
-                        debug!("Actual ROBOTS permissions {:?}", diff_robots);
-
-                        if let Some(user) = configured_repo.permissions.as_ref() {
-                            for el_permission in &user.robots {
-                                diff_robots.retain(|x| x != el_permission);
-                            }
-                            //println!("Wanted ROBOT permissions {:?}", &user.robots);
-                        } else {
-                            //println!("Wanted ROBOT permissions: NONE");
-                        }
+        Ok(response.clone())
+    }
+    async fn grant_team_permission_to_repository(
+        &self,
+        repo: &str,
+        user: &UserElement,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "team",
+            &user.name,
+        ])?;
+        let mut body = HashMap::new();
+        body.insert("role", &user.role);
 
-                        //println!("Difference ROBOTS permissions {:?}", diff_robots);
-
-                        //Fix the robot name
-                        let diff_fixed_robots = diff_robots.iter().map(|robot| UserElement {
-                            name: format!("{}+{}", &self.quay_organization, robot.name),
-                            role: robot.role.to_owned(),
-                        });
-                        for robot in diff_fixed_robots {
-                            self.delete_user_permission_from_repository(
-                                &repo.name,
-                                &robot,
-                                quay_fn_arguments.clone(),
-                            )
-                            .await?;
-                        }
-                        //delete_user_permission_to_repository
-                    }
-                    None => {}
-                }
-            }
-            _ => {}
-        }
+        let response = &self
+            .send_request(
+                endpoint,
+                &body,
+                &self.quay_organization,
+                Method::PUT,
+                quay_fn_arguments,
+            )
+            .await?;
 
         Ok(response.clone())
     }
-
-    async fn get_team_permission_from_repository(
+    async fn delete_organization(
         &self,
-        repo: &Repository,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/team/",
-            &self.quay_endpoint, &self.quay_organization, repo.name,
-        );
+        let endpoint = self.api_url(&["organization", &self.quay_organization])?;
         let body: HashMap<&str, &String> = HashMap::new();
+
         let response = &self
             .send_request(
                 endpoint,
                 &body,
                 &self.quay_organization,
-                Method::GET,
-                quay_fn_arguments.clone(),
+                Method::DELETE,
+                quay_fn_arguments,
             )
             .await?;
 
-        ////println!("{} - {}", &self.quay_organization, repo.name);
-        match response.status_code {
-            StatusCode::OK => {
-                let mut actual_repo_permissions = Vec::new();
-                //println!();
-                //println!("####################");
-                //println!(
-                //    "Organization {} :: Repository: {} ",
-                //    &self.quay_organization, repo.name
-                // );
-                //println!("####################");
-                //println!();
-                //For team
-                if let Some(objs) = response.response.as_object() {
-                    if let Some(objs_permissions) = objs["permissions"].as_object() {
-                        for (_, v) in objs_permissions.iter() {
-                            if let Some(name) = v["name"].as_str() {
-                                if let Some(role) = v["role"].as_str() {
-                                    let single_team_permission =
-                                        UserElement::new(name.to_string(), role.to_string());
-                                    actual_repo_permissions.push(single_team_permission);
-                                }
-                            }
-                        }
-                    }
-                }
+        Ok(response.clone())
+    }
+    async fn delete_robot(
+        &self,
+        robot: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["organization", &self.quay_organization, "robots", robot])?;
+        let body: HashMap<&str, &String> = HashMap::new();
 
-                //println!("Actual permissions: {:?}", actual_repo_permissions);
-                //println!("---------");
-                let configured_repository = self.repositories.iter().find(|r| r == &repo);
-                ////println!("{:?}",configured_repository);
-                match configured_repository {
-                    Some(configured_repo) => {
-                        //println!("Actual TEAMS permissions {:?}", actual_repo_permissions);
-
-                        let mut diff_teams = actual_repo_permissions;
-                        if let Some(user) = configured_repo.permissions.as_ref() {
-                            if let Some(teams) = &user.teams {
-                                for el_permission in teams {
-                                    diff_teams.retain(|x| x != el_permission);
-                                }
-                                //println!("Wanted TEAMS permissions {:?}", &user.teams);
-                            }
-                        }
+        let description = format!(
+            "Deleting robot '{}' of organization '{}'",
+            robot, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::DELETE,
+                quay_fn_arguments,
+            )
+            .await?;
 
-                        //println!("Difference TEAMS permissions {:?}", diff_teams);
+        Ok(response)
+    }
 
-                        for team in diff_teams {
-                            self.delete_team_permission_from_repository(
-                                &repo.name,
-                                &team,
-                                quay_fn_arguments.clone(),
-                            )
-                            .await?;
-                        }
+    async fn delete_team(
+        &self,
+        team: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["organization", &self.quay_organization, "team", team])?;
+        let body: HashMap<&str, &String> = HashMap::new();
 
-                        //println!();
-                    }
-                    None => {
-                        //println!("No teams present")
-                    }
-                }
-            }
-            _ => {}
-        }
+        let description = format!(
+            "Deleting team '{}' of organization '{}'",
+            team, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::DELETE,
+                quay_fn_arguments,
+            )
+            .await?;
 
-        Ok(response.clone())
+        Ok(response)
     }
 
-    async fn grant_robot_permission_to_repository(
+    async fn delete_user_from_team(
         &self,
-        repo: &String,
-        user: &UserElement,
+        team: &str,
+        user: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/user/{}",
-            &self.quay_endpoint,
+        let endpoint = self.api_url(&[
+            "organization",
             &self.quay_organization,
-            repo,
-            format!("{}+{}", &self.quay_organization, user.name)
-        );
-        let mut body = HashMap::new();
-        body.insert("role", &user.role);
+            "team",
+            team,
+            "members",
+            user,
+        ])?;
+        let body: HashMap<&str, &String> = HashMap::new();
 
-        let response = &self
+        let description = format!(
+            "Deleting user '{}' from team '{}' of organization '{}'",
+            user, team, &self.quay_organization
+        );
+        let response = self
             .send_request(
                 endpoint,
                 &body,
-                &self.quay_organization,
-                Method::PUT,
+                &description,
+                Method::DELETE,
                 quay_fn_arguments,
             )
             .await?;
 
-        Ok(response.clone())
+        Ok(response)
     }
-    async fn grant_team_permission_to_repository(
+
+    async fn delete_robot_from_team(
         &self,
-        repo: &String,
-        user: &UserElement,
+        team: &str,
+        robot: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/permissions/team/{}",
-            &self.quay_endpoint, &self.quay_organization, repo, user.name
-        );
-        let mut body = HashMap::new();
-        body.insert("role", &user.role);
+        let endpoint = self.api_url(&[
+            "organization",
+            &self.quay_organization,
+            "team",
+            team,
+            "members",
+            &format!("{}+{}", &self.quay_organization, robot),
+        ])?;
+        let body: HashMap<&str, &String> = HashMap::new();
 
-        let response = &self
+        let description = format!(
+            "Deleting robot '{}' from team '{}' of organization '{}'",
+            robot, team, &self.quay_organization
+        );
+        let response = self
             .send_request(
                 endpoint,
                 &body,
-                &self.quay_organization,
-                Method::PUT,
+                &description,
+                Method::DELETE,
                 quay_fn_arguments,
             )
             .await?;
 
-        Ok(response.clone())
+        Ok(response)
     }
-    async fn delete_organization(
+
+    async fn delete_repository(
         &self,
+        repo: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/organization/{}",
-            &self.quay_endpoint, &self.quay_organization
-        );
+        let endpoint = self.api_url(&["repository", &self.quay_organization, repo])?;
         let body: HashMap<&str, &String> = HashMap::new();
 
-        let response = &self
+        let description = format!(
+            "Deleting repository '{}' of organization '{}'",
+            repo, &self.quay_organization
+        );
+        let response = self
             .send_request(
                 endpoint,
                 &body,
-                &self.quay_organization,
+                &description,
                 Method::DELETE,
                 quay_fn_arguments,
             )
             .await?;
 
-        Ok(response.clone())
+        Ok(response)
     }
+
     async fn create_robot(
         &self,
         robot: &RobotDetails,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/organization/{}/robots/{}",
-            &self.quay_endpoint, &self.quay_organization, robot.name
-        );
+        let endpoint = self.api_url(&[
+            "organization",
+            &self.quay_organization,
+            "robots",
+            &robot.name,
+        ])?;
         let mut body: HashMap<&str, &String> = HashMap::new();
 
         body.insert("description", &robot.desc);
@@ -605,10 +706,8 @@ impl Actions for OrganizationYaml {
         team: &Team,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/organization/{}/team/{}",
-            &self.quay_endpoint, &self.quay_organization, team.name
-        );
+        let endpoint =
+            self.api_url(&["organization", &self.quay_organization, "team", &team.name])?;
         let mut body = HashMap::new();
 
         body.insert("description", &team.description);
@@ -634,14 +733,18 @@ impl Actions for OrganizationYaml {
 
     async fn add_user_to_team(
         &self,
-        team: &String,
-        user: &String,
+        team: &str,
+        user: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/organization/{}/team/{}/members/{}",
-            &self.quay_endpoint, &self.quay_organization, team, user
-        );
+        let endpoint = self.api_url(&[
+            "organization",
+            &self.quay_organization,
+            "team",
+            team,
+            "members",
+            user,
+        ])?;
         let body: HashMap<&str, &String> = HashMap::new();
 
         let response = &self
@@ -659,17 +762,18 @@ impl Actions for OrganizationYaml {
 
     async fn add_robot_to_team(
         &self,
-        team: &String,
-        robot: &String,
+        team: &str,
+        robot: &str,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/organization/{}/team/{}/members/{}",
-            &self.quay_endpoint,
+        let endpoint = self.api_url(&[
+            "organization",
             &self.quay_organization,
+            "team",
             team,
-            format!("{}+{}", &self.quay_organization, robot)
-        );
+            "members",
+            &format!("{}+{}", &self.quay_organization, robot),
+        ])?;
         let body: HashMap<&str, &String> = HashMap::new();
 
         let response = &self
@@ -689,7 +793,7 @@ impl Actions for OrganizationYaml {
         repo: &Repository,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!("https://{}/api/v1/repository", &self.quay_endpoint,);
+        let endpoint = self.api_url(&["repository"])?;
         let mut body: HashMap<&str, &String> = HashMap::new();
 
         let repo_kind = String::from("image");
@@ -729,10 +833,8 @@ impl Actions for OrganizationYaml {
         repo: &Repository,
         quay_fn_arguments: QuayFnArguments,
     ) -> Result<QuayResponse, Box<dyn Error>> {
-        let endpoint = format!(
-            "https://{}/api/v1/repository/{}/{}/mirror",
-            &self.quay_endpoint, &self.quay_organization, repo.name
-        );
+        let endpoint =
+            self.api_url(&["repository", &self.quay_organization, &repo.name, "mirror"])?;
 
         //println!("{}", endpoint);
 
@@ -768,22 +870,16 @@ impl Actions for OrganizationYaml {
                 let mut external_registry_password: Option<String> = None;
                 // Get the appropriate repository mirror password
 
-                match &quay_fn_arguments.mirror_login {
-                    Some(mirrors) => {
-                        for mirror in mirrors {
-                            if Some(mirror.ext_registry_username.clone())
-                                == params.ext_registry_username.clone()
-                            {
-                                if mirror.repository == repo.name {
-                                    if mirror.organization == self.quay_organization {
-                                        external_registry_password =
-                                            Some(mirror.ext_registry_password.clone());
-                                    }
-                                }
-                            }
+                if let Some(mirrors) = &quay_fn_arguments.mirror_login {
+                    for mirror in mirrors {
+                        if Some(mirror.ext_registry_username.clone())
+                            == params.ext_registry_username.clone()
+                            && mirror.repository == repo.name
+                            && mirror.organization == self.quay_organization
+                        {
+                            external_registry_password = Some(mirror.ext_registry_password.clone());
                         }
                     }
-                    None => {}
                 }
 
                 let body = MirrorConfig {
@@ -804,6 +900,7 @@ impl Actions for OrganizationYaml {
                     ),
                     external_registry_config,
                     root_rule,
+                    is_enabled: params.is_enabled,
                 };
 
                 //println!("{}", serde_json::to_string(&body).unwrap());
@@ -815,10 +912,12 @@ impl Actions for OrganizationYaml {
 
                 //Change repository state to mirror
 
-                let endpoint_state = format!(
-                    "https://{}/api/v1/repository/{}/{}/changestate",
-                    &self.quay_endpoint, &self.quay_organization, repo.name
-                );
+                let endpoint_state = self.api_url(&[
+                    "repository",
+                    &self.quay_organization,
+                    &repo.name,
+                    "changestate",
+                ])?;
 
                 let mut body_state: HashMap<&str, &str> = HashMap::new();
 
@@ -868,6 +967,7 @@ impl Actions for OrganizationYaml {
                     response: Value::Null,
                     description: String::from("Mirroring disabled"),
                     status_code: StatusCode::OK,
+                    ..Default::default()
                 };
                 return Ok(response.clone());
             }
@@ -875,9 +975,339 @@ impl Actions for OrganizationYaml {
 
         //body.insert("unstructured_metadata", empty);
     }
+
+    async fn update_repository(
+        &self,
+        repo: &Repository,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["repository", &self.quay_organization, &repo.name])?;
+        let mut body: HashMap<&str, &String> = HashMap::new();
+
+        let empty = String::from("");
+        body.insert("description", repo.description.as_ref().unwrap_or(&empty));
+
+        let description = format!(
+            "Updating repository '{}' for organization '{}'",
+            repo.name, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::PUT,
+                quay_fn_arguments.clone(),
+            )
+            .await?;
+
+        if !response.status_code.is_success() {
+            return Ok(response);
+        }
+
+        let endpoint_visibility = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            &repo.name,
+            "changevisibility",
+        ])?;
+        let mut body_visibility: HashMap<&str, &str> = HashMap::new();
+        body_visibility.insert("visibility", repo.get_visibility());
+
+        let response = self
+            .send_request(
+                endpoint_visibility,
+                &body_visibility,
+                &description,
+                Method::POST,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_user(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["user", ""])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!("Reading user of endpoint '{}'", &self.quay_endpoint);
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_organization(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["organization", &self.quay_organization])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!("Reading organization '{}'", &self.quay_organization);
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_organization_robots(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["organization", &self.quay_organization, "robots"])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!(
+            "Reading robots of organization '{}'",
+            &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_team_members(
+        &self,
+        team: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&[
+            "organization",
+            &self.quay_organization,
+            "team",
+            team,
+            "members",
+        ])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!(
+            "Reading members of team '{}' for organization '{}'",
+            team, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_organization_repositories(
+        &self,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let body: HashMap<&str, &String> = HashMap::new();
+        let description = format!(
+            "Reading repositories of organization '{}'",
+            &self.quay_organization
+        );
+
+        // Quay paginates the repository list, all the pages are merged in a single response.
+        let mut repositories: Vec<Value> = Vec::new();
+        let mut next_page: Option<String> = None;
+
+        loop {
+            let mut endpoint = self.api_url(&["repository"])?;
+            endpoint
+                .query_pairs_mut()
+                .append_pair("namespace", &self.quay_organization);
+            if let Some(page) = &next_page {
+                endpoint.query_pairs_mut().append_pair("next_page", page);
+            }
+
+            let response = self
+                .send_request(
+                    endpoint,
+                    &body,
+                    &description,
+                    Method::GET,
+                    quay_fn_arguments.clone(),
+                )
+                .await?;
+
+            if !response.status_code.is_success() {
+                return Ok(response);
+            }
+
+            if let Some(page) = response.response["repositories"].as_array() {
+                repositories.extend(page.iter().cloned());
+            }
+
+            match response.response["next_page"].as_str() {
+                Some(page) => next_page = Some(page.to_string()),
+                None => {
+                    return Ok(QuayResponse {
+                        response: serde_json::json!({ "repositories": repositories }),
+                        status_code: response.status_code,
+                        description,
+                        method: response.method,
+                        attempts: response.attempts,
+                    });
+                }
+            }
+        }
+    }
+
+    async fn get_repository_user_permissions(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "user",
+            "",
+        ])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!(
+            "Reading user permissions of repository '{}' for organization '{}'",
+            repo, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_repository_team_permissions(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&[
+            "repository",
+            &self.quay_organization,
+            repo,
+            "permissions",
+            "team",
+            "",
+        ])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!(
+            "Reading team permissions of repository '{}' for organization '{}'",
+            repo, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
+
+    async fn get_repository_mirror(
+        &self,
+        repo: &str,
+        quay_fn_arguments: QuayFnArguments,
+    ) -> Result<QuayResponse, Box<dyn Error>> {
+        let endpoint = self.api_url(&["repository", &self.quay_organization, repo, "mirror"])?;
+        let body: HashMap<&str, &String> = HashMap::new();
+
+        let description = format!(
+            "Reading mirror configuration of repository '{}' for organization '{}'",
+            repo, &self.quay_organization
+        );
+        let response = self
+            .send_request(
+                endpoint,
+                &body,
+                &description,
+                Method::GET,
+                quay_fn_arguments,
+            )
+            .await?;
+
+        Ok(response)
+    }
 }
 
 impl OrganizationYaml {
+    /// Organization without objects, used as a starting point to read a live organization.
+    pub fn new(quay_endpoint: &str, quay_organization: &str) -> OrganizationYaml {
+        OrganizationYaml {
+            quay_endpoint: quay_endpoint.to_string(),
+            replicate_to: None,
+            quay_validate_certs: "yes".to_string(),
+            quay_prune: None,
+            quay_prune_objects: None,
+            quay_organization: quay_organization.to_string(),
+            quay_organization_role_name: quay_organization.to_string(),
+            quay_organization_role_email: String::new(),
+            repositories: Vec::new(),
+            robots: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    /// URL of a Quay API path, built from the endpoint base URL.
+    ///
+    /// Every segment is percent-encoded, repository names are split on '/' so nested
+    /// repositories like 'nested/demorepo' keep their path. An empty last segment adds a trailing slash.
+    pub fn api_url(&self, segments: &[&str]) -> Result<Url, Box<dyn Error>> {
+        let mut url = api_base_url(&self.quay_endpoint)?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid Quay endpoint '{}'", self.quay_endpoint))?
+            .extend(segments.iter().flat_map(|segment| segment.split('/')));
+        Ok(url)
+    }
+
+    /// False if quay_validate_certs disables the verification of the Quay certificate.
+    pub fn validate_certs(&self) -> bool {
+        !matches!(
+            self.quay_validate_certs.trim().to_lowercase().as_str(),
+            "no" | "false" | "off" | "0"
+        )
+    }
+
     pub fn change_endpoint(&mut self, endpoint: String) {
         self.quay_endpoint = endpoint;
     }
@@ -885,27 +1315,99 @@ impl OrganizationYaml {
     pub fn get_quay_endpoint(&self) -> String {
         self.quay_endpoint.clone()
     }
+
+    /// True if objects of this type not declared in the yaml configuration must be deleted.
+    ///
+    /// Without quay_prune only repository permissions are pruned, with quay_prune enabled
+    /// the types listed in quay_prune_objects are pruned (all types if not specified).
+    pub fn is_prunable(&self, object: PruneObject) -> bool {
+        match self.quay_prune {
+            None => object == PruneObject::Permissions,
+            Some(false) => false,
+            Some(true) => self
+                .quay_prune_objects
+                .as_ref()
+                .is_none_or(|objects| objects.contains(&object)),
+        }
+    }
+
+    /// Removes the organization+ prefix from the robots referenced by the permissions, the team
+    /// members and the mirrors, as the requests add it to the robot names.
+    pub fn strip_robot_prefixes(&mut self) {
+        let prefix = format!("{}+", self.quay_organization);
+        let strip = |name: &mut String| {
+            if let Some(short) = name.strip_prefix(&prefix) {
+                *name = short.to_string();
+            }
+        };
+
+        for repo in &mut self.repositories {
+            if let Some(mirror) = &mut repo.mirror_params {
+                strip(&mut mirror.robot_username);
+            }
+            if let Some(permissions) = &mut repo.permissions {
+                permissions
+                    .robots
+                    .iter_mut()
+                    .for_each(|r| strip(&mut r.name));
+            }
+        }
+        for team in &mut self.teams {
+            team.members.robots.iter_mut().for_each(strip);
+        }
+    }
+
+    /// True if at least one object type is pruned.
+    pub fn is_pruning_enabled(&self) -> bool {
+        self.quay_prune != Some(false)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Object types that can be pruned.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneObject {
+    Robots,
+    Teams,
+    TeamMembers,
+    Repositories,
+    Permissions,
+}
+
+/// Quay organization, read from a yaml file.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct OrganizationYaml {
+    /// Quay endpoint of the organization, as host[:port] or https://host[:port]
     #[serde(rename = "quay_endpoint")]
     pub quay_endpoint: String,
 
-    #[serde(rename = "replicate_to")]
+    /// Quay endpoints the organization is replicated to, besides quay_endpoint
+    #[serde(rename = "replicate_to", skip_serializing_if = "Option::is_none")]
     pub replicate_to: Option<Vec<String>>,
 
+    /// Verify the tls certificate of the Quay endpoints, unless "no" or "false"
     #[serde(rename = "quay_validate_certs")]
-    quay_validate_certs: String,
+    pub quay_validate_certs: String,
+
+    /// Delete the objects of the organization missing from the file: true deletes the types of quay_prune_objects,
+    /// false deletes nothing. Default to deleting only the repository permissions
+    #[serde(rename = "quay_prune", skip_serializing_if = "Option::is_none")]
+    pub quay_prune: Option<bool>,
+
+    /// Object types deleted when quay_prune is true. Default to all
+    #[serde(rename = "quay_prune_objects", skip_serializing_if = "Option::is_none")]
+    pub quay_prune_objects: Option<Vec<PruneObject>>,
 
+    /// Organization name
     #[serde(rename = "quay_organization")]
     pub quay_organization: String,
 
     #[serde(rename = "quay_organization_role_name")]
-    quay_organization_role_name: String,
+    pub quay_organization_role_name: String,
 
     #[serde(rename = "quay_organization_role_email")]
-    quay_organization_role_email: String,
+    pub quay_organization_role_email: String,
 
     #[serde(rename = "repositories")]
     pub repositories: Vec<Repository>,
@@ -917,24 +1419,33 @@ pub struct OrganizationYaml {
     pub teams: Vec<Team>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct Repository {
+    /// Repository name, lowercase letters, digits, '.', '_', '-' and '/'
     #[serde(rename = "name")]
     pub name: String,
 
-    #[serde(rename = "description")]
-    description: Option<String>,
+    #[serde(rename = "description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 
-    #[serde(rename = "visibility")]
-    visibility: Option<String>,
+    /// public or private. Default to public
+    #[serde(
+        rename = "visibility",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "organization_schema::visibility")]
+    pub visibility: Option<String>,
 
+    /// Mirror the repository from mirror_params
     #[serde(rename = "mirror")]
-    mirror: bool,
+    pub mirror: bool,
 
-    #[serde(rename = "mirror_params")]
+    #[serde(rename = "mirror_params", skip_serializing_if = "Option::is_none")]
     pub mirror_params: Option<MirrorParams>,
 
-    #[serde(rename = "permissions")]
+    #[serde(rename = "permissions", skip_serializing_if = "Option::is_none")]
     pub permissions: Option<Permissions>,
 }
 /*
@@ -982,6 +1493,8 @@ pub struct MirrorConfig {
     pub external_registry_config: ExternalRegistryConfig,
     #[serde(rename = "root_rule")]
     pub root_rule: RootRule,
+    #[serde(rename = "is_enabled", default)]
+    pub is_enabled: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1014,60 +1527,96 @@ pub struct RootRule {
     pub rule_value: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+/// Source of a mirrored repository.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct MirrorParams {
+    /// Registry of the mirrored image, as quay.io
     #[serde(rename = "src_registry")]
-    src_registry: String,
+    pub src_registry: String,
 
+    /// Mirrored image, without registry and tag
     #[serde(rename = "src_image")]
-    src_image: String,
+    pub src_image: String,
 
+    /// Tags mirrored, glob patterns are accepted
     #[serde(rename = "src_image_tags")]
-    src_image_tags: Vec<String>,
+    pub src_image_tags: Vec<String>,
 
     #[serde(rename = "ext_registry_verify_tls")]
-    ext_registry_verify_tls: bool,
+    pub ext_registry_verify_tls: bool,
 
-    #[serde(rename = "ext_registry_unsigned_image")]
-    ext_registry_unsigned_image: Option<bool>,
+    #[serde(
+        rename = "ext_registry_unsigned_image",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ext_registry_unsigned_image: Option<bool>,
 
+    /// Robot of the organization writing the mirrored images, by its name in robots.
+    /// An organization+ prefix is removed
     #[serde(rename = "robot_username")]
-    robot_username: String,
+    pub robot_username: String,
 
+    /// Seconds between two synchronizations
     #[serde(rename = "sync_interval")]
-    sync_interval: i64,
+    #[schemars(range(min = 1))]
+    pub sync_interval: i64,
 
     #[serde(rename = "is_enabled")]
-    is_enabled: bool,
+    pub is_enabled: bool,
 
-    #[serde(rename = "https_proxy")]
-    https_proxy: Option<String>,
+    #[serde(rename = "https_proxy", skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<String>,
 
-    #[serde(rename = "http_proxy")]
-    http_proxy: Option<String>,
+    #[serde(rename = "http_proxy", skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
 
-    #[serde(rename = "no_proxy")]
-    no_proxy: Option<String>,
+    #[serde(rename = "no_proxy", skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
 
-    #[serde(rename = "ext_registry_username")]
+    /// User of the source registry. Its password is asked by qcli login
+    #[serde(
+        rename = "ext_registry_username",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub ext_registry_username: Option<String>,
 
-    #[serde(rename = "ext_registry_password")]
-    ext_registry_password: Option<String>,
+    /// Prefer qcli login, to keep the password out of the yaml files
+    #[serde(
+        rename = "ext_registry_password",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ext_registry_password: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+/// Roles of robots, users and teams on a repository.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct Permissions {
+    /// Robots of the organization, by their name in robots. An organization+ prefix is removed
     #[serde(rename = "robots")]
     pub robots: Vec<UserElement>,
 
     #[serde(rename = "users")]
     pub users: Vec<UserElement>,
 
-    #[serde(rename = "teams")]
+    /// Teams of the organization, or owners
+    #[serde(rename = "teams", skip_serializing_if = "Option::is_none")]
     pub teams: Option<Vec<UserElement>>,
 }
 
+impl Repository {
+    /// Repository visibility, Quay repositories are public if not specified.
+    pub fn get_visibility(&self) -> &str {
+        self.visibility.as_deref().unwrap_or("public")
+    }
+
+    /// Repository description, empty if not specified.
+    pub fn get_description(&self) -> &str {
+        self.description.as_deref().unwrap_or_default()
+    }
+}
+
 impl Permissions {
     pub fn new() -> Permissions {
         Permissions {
@@ -1078,12 +1627,14 @@ impl Permissions {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct UserElement {
     #[serde(rename = "name")]
     pub name: String,
 
     #[serde(rename = "role")]
+    #[schemars(schema_with = "organization_schema::repository_role")]
     pub role: String,
 }
 
@@ -1093,66 +1644,82 @@ impl UserElement {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct RobotDetails {
+    /// Robot name, without the organization+ prefix
     #[serde(rename = "name")]
     pub name: String,
 
+    /// Robot description
     #[serde(rename = "desc")]
     pub desc: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct Team {
     #[serde(rename = "name")]
     pub name: String,
 
     #[serde(rename = "description")]
-    description: String,
+    pub description: String,
 
     #[serde(rename = "members")]
     pub members: Members,
 
+    /// Role of the team in the organization
     #[serde(rename = "role")]
-    role: String,
+    #[schemars(schema_with = "organization_schema::team_role")]
+    pub role: String,
 }
 
 /// Repository's member structs.
 /// In the Quay context, a 'member' could be a user or a robot.
 ///
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct Members {
     #[serde(rename = "users")]
     pub users: Vec<String>,
 
+    /// Robots of the organization, by their name in robots. An organization+ prefix is removed
     #[serde(rename = "robots")]
     pub robots: Vec<String>,
 }
 
+/// Rate limiter of a single Quay endpoint.
+pub type QuayRateLimiter =
+    RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>;
+
 /// Helper struct to pass arguments to functions.
 /// Useful for easily extend accepted function arguments.
 #[derive(Debug, Clone)]
 pub struct QuayFnArguments {
     /// Quay token
     pub(crate) token: String,
-    /// Rate limiter
-    pub governor:
-        Arc<RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>>,
+    /// Rate limiter of the organization's endpoint
+    pub governor: Arc<QuayRateLimiter>,
     /// Log level
     pub log_level: log::Level,
     /// Log verbosity
     pub log_verbosity: u8,
-    /// Connection timeout in seconds
-    pub timeout: u64,
-    /// Verify Quay tls
-    pub tls_verify: bool,
+    /// Pooled HTTP client of the organization's endpoint
+    pub client: reqwest::Client,
 
     pub mirror_login: Option<Vec<MirrorLogin>>,
-}
-
-#[derive(Debug, Clone)]
-pub struct QuayFnArgumentsMirrorLogin {
-    pub repo: String,
-    pub user: String,
-    pub password: String,
+    /// Limit of in flight requests shared by every endpoint
+    pub global_limit: Arc<Semaphore>,
+    /// Limit of in flight requests to the organization's endpoint
+    pub endpoint_limit: Arc<Semaphore>,
+    /// Max number of tasks of an organization executed at the same time
+    pub concurrency: usize,
+    /// Retry policy of failed requests
+    pub retry_policy: RetryPolicy,
+    /// With --fail-fast, flag shared by every organization and set by the first failed task
+    pub fail_fast: Option<Arc<AtomicBool>>,
+    /// Live progress bars, `None` if stdout is not a terminal
+    pub progress: Option<RunProgress>,
+    /// Requests sent to the endpoint, counted for the progress bars
+    pub sent_requests: Option<Arc<AtomicU64>>,
 }
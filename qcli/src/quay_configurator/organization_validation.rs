@@ -0,0 +1,284 @@
+use super::organization_state::OWNERS_TEAM;
+use super::organization_struct::{OrganizationYaml, UserElement};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Roles of users, robots and teams on a repository.
+pub(super) const REPOSITORY_ROLES: [&str; 3] = ["read", "write", "admin"];
+
+/// Roles of a team in its organization.
+pub(super) const TEAM_ROLES: [&str; 3] = ["member", "creator", "admin"];
+
+pub(super) const VISIBILITIES: [&str; 2] = ["public", "private"];
+
+/// Problem found in an organization yaml file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Line of the yaml file, starting from 1, if found
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Lines of a yaml file, searched to point the problems at the line of the wrong value.
+struct YamlLines<'a> {
+    lines: Vec<&'a str>,
+}
+
+impl YamlLines<'_> {
+    /// Returns the index of the first line from `from` with `key: value`, or `- value` without
+    /// a key. Quotes, list dashes and trailing comments are ignored.
+    fn find(&self, from: usize, key: Option<&str>, value: &str) -> Option<usize> {
+        let unquote = |v: &str| v.trim().trim_matches('"').trim_matches('\'').to_string();
+
+        (from..self.lines.len()).find(|&index| {
+            let mut line = self.lines[index].trim();
+            if let Some(comment) = line.find(" #") {
+                line = line[..comment].trim_end();
+            }
+            let line = line.strip_prefix("- ").unwrap_or(line).trim_start();
+            match key {
+                Some(key) => line
+                    .strip_prefix(key)
+                    .and_then(|rest| rest.strip_prefix(':'))
+                    .is_some_and(|rest| unquote(rest) == value),
+                None => unquote(line) == value,
+            }
+        })
+    }
+
+    /// Follows the steps from the line `from`, each searched from the line of the previous one.
+    /// Returns the line of the last step found, `None` if the first step is not found.
+    fn locate(&self, from: usize, steps: &[(Option<&str>, &str)]) -> Option<usize> {
+        let mut line = from;
+        for (index, (key, value)) in steps.iter().enumerate() {
+            match self.find(line, *key, value) {
+                Some(found) => line = found,
+                None if index == 0 => return None,
+                None => break,
+            }
+        }
+        Some(line)
+    }
+}
+
+/// Returns the reason why a repository name is not accepted by Quay.
+fn invalid_repository_name(name: &str) -> Option<&'static str> {
+    if name.is_empty() || name.len() > 255 {
+        return Some("it must be 1 to 255 characters long");
+    }
+    for component in name.split('/') {
+        if !component
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+        {
+            return Some("only lowercase letters, digits, '.', '_', '-' and '/' are allowed");
+        }
+        if !component
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+        {
+            return Some("each part must start with a letter or a digit");
+        }
+    }
+    None
+}
+
+impl OrganizationYaml {
+    /// Checks the references between the objects of the organization, and the values Quay
+    /// would reject. `yaml` is the file the organization was read from, to find the lines.
+    pub fn validate(&self, yaml: &str) -> Vec<Problem> {
+        let lines = YamlLines {
+            lines: yaml.lines().collect(),
+        };
+        let mut problems = Vec::new();
+        let mut problem = |index: Option<usize>, message: String| {
+            problems.push(Problem {
+                line: index.map(|index| index + 1),
+                message,
+            })
+        };
+
+        let robots: HashSet<&str> = self.robots.iter().map(|r| r.name.as_str()).collect();
+        let teams: HashSet<&str> = self.teams.iter().map(|t| t.name.as_str()).collect();
+        // The organization+ prefix of the robot names is removed when the file is loaded
+        let is_robot = |name: &str| robots.contains(name);
+
+        if let Some(replicate_to) = &self.replicate_to {
+            let mut seen = HashSet::new();
+            let mut next_line = lines.find(0, Some("replicate_to"), "");
+            for endpoint in replicate_to {
+                let line = next_line.and_then(|from| lines.find(from, None, endpoint));
+                next_line = line.map(|line| line + 1).or(next_line);
+                if endpoint == &self.quay_endpoint {
+                    problem(
+                        line,
+                        format!("replicate_to contains the quay_endpoint '{}'", endpoint),
+                    );
+                } else if !seen.insert(endpoint) {
+                    problem(
+                        line,
+                        format!("replicate_to contains '{}' more than once", endpoint),
+                    );
+                }
+            }
+        }
+
+        let mut repositories = HashSet::new();
+        // Each repository is searched after the previous one, as they are in the file order
+        let mut next_line = lines.find(0, Some("repositories"), "");
+        for repo in &self.repositories {
+            let repo_line = next_line.and_then(|from| lines.find(from, Some("name"), &repo.name));
+            next_line = repo_line.map(|line| line + 1).or(next_line);
+            let at = |steps: &[(Option<&str>, &str)]| {
+                repo_line.and_then(|start| lines.locate(start, steps).or(Some(start)))
+            };
+
+            if !repositories.insert(repo.name.as_str()) {
+                problem(
+                    at(&[]),
+                    format!("repository '{}' is declared more than once", repo.name),
+                );
+            }
+            if let Some(reason) = invalid_repository_name(&repo.name) {
+                problem(
+                    at(&[]),
+                    format!("repository name '{}' is not valid: {}", repo.name, reason),
+                );
+            }
+            if let Some(visibility) = &repo.visibility {
+                if !VISIBILITIES.contains(&visibility.as_str()) {
+                    problem(
+                        at(&[(Some("visibility"), visibility)]),
+                        format!(
+                            "visibility '{}' of repository '{}' is not public or private",
+                            visibility, repo.name
+                        ),
+                    );
+                }
+            }
+
+            if let Some(mirror) = &repo.mirror_params {
+                if !is_robot(&mirror.robot_username) {
+                    problem(
+                        at(&[(Some("robot_username"), &mirror.robot_username)]),
+                        format!(
+                            "robot '{}' of the mirror of repository '{}' is not declared in robots",
+                            mirror.robot_username, repo.name
+                        ),
+                    );
+                }
+                if mirror.sync_interval <= 0 {
+                    problem(
+                        at(&[(Some("sync_interval"), &mirror.sync_interval.to_string())]),
+                        format!(
+                            "sync_interval of the mirror of repository '{}' must be positive",
+                            repo.name
+                        ),
+                    );
+                }
+            }
+
+            let Some(permissions) = &repo.permissions else {
+                continue;
+            };
+            let mut check_roles = |kind: &str, elements: &[UserElement]| {
+                for element in elements {
+                    if !REPOSITORY_ROLES.contains(&element.role.as_str()) {
+                        problem(
+                            at(&[
+                                (Some("permissions"), ""),
+                                (Some("name"), &element.name),
+                                (Some("role"), &element.role),
+                            ]),
+                            format!(
+                                "role '{}' of {} '{}' on repository '{}' is not one of {}",
+                                element.role,
+                                kind,
+                                element.name,
+                                repo.name,
+                                REPOSITORY_ROLES.join(", ")
+                            ),
+                        );
+                    }
+                }
+            };
+            check_roles("robot", &permissions.robots);
+            check_roles("user", &permissions.users);
+            check_roles("team", permissions.teams.as_deref().unwrap_or_default());
+
+            for robot in permissions.robots.iter().filter(|r| !is_robot(&r.name)) {
+                problem(
+                    at(&[
+                        (Some("permissions"), ""),
+                        (Some("robots"), ""),
+                        (Some("name"), &robot.name),
+                    ]),
+                    format!(
+                        "robot '{}' of the permissions of repository '{}' is not declared in robots",
+                        robot.name, repo.name
+                    ),
+                );
+            }
+            for team in permissions
+                .teams
+                .iter()
+                .flatten()
+                .filter(|t| !teams.contains(t.name.as_str()) && t.name != OWNERS_TEAM)
+            {
+                problem(
+                    at(&[
+                        (Some("permissions"), ""),
+                        (Some("teams"), ""),
+                        (Some("name"), &team.name),
+                    ]),
+                    format!(
+                        "team '{}' of the permissions of repository '{}' is not declared in teams",
+                        team.name, repo.name
+                    ),
+                );
+            }
+        }
+
+        let mut next_line = lines.find(0, Some("teams"), "");
+        for team in &self.teams {
+            let team_line = next_line.and_then(|from| lines.find(from, Some("name"), &team.name));
+            next_line = team_line.map(|line| line + 1).or(next_line);
+            let at = |steps: &[(Option<&str>, &str)]| {
+                team_line.and_then(|start| lines.locate(start, steps).or(Some(start)))
+            };
+
+            if !TEAM_ROLES.contains(&team.role.as_str()) {
+                problem(
+                    at(&[(Some("role"), &team.role)]),
+                    format!(
+                        "role '{}' of team '{}' is not one of {}",
+                        team.role,
+                        team.name,
+                        TEAM_ROLES.join(", ")
+                    ),
+                );
+            }
+            for robot in team.members.robots.iter().filter(|r| !is_robot(r)) {
+                problem(
+                    at(&[(Some("robots"), ""), (None, robot)]),
+                    format!(
+                        "robot '{}' member of team '{}' is not declared in robots",
+                        robot, team.name
+                    ),
+                );
+            }
+        }
+
+        problems
+    }
+}
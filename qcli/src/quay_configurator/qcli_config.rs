@@ -0,0 +1,118 @@
+use super::run_report::OutputFormat;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Env variable with the path of the qcli config file.
+pub const CONFIG_ENV: &str = "QCLI_CONFIG";
+
+/// Login file of the previous releases, relative to the current directory.
+/// Still used when it exists, so existing setups keep working.
+const LEGACY_LOGIN_FILE: &str = ".qcli/login.yaml";
+
+/// Defaults of the command line options, read from `qcli.toml`.
+///
+/// Each key is named after its command line option. Command line options and their env
+/// variables override the config file, the config file overrides the built-in defaults.
+/// ```toml
+/// log_level = "debug"
+/// output = "json"
+/// timeout = 10
+/// tls_verify = true
+/// ca_bundle = "/etc/pki/quay-ca.pem"
+/// rate_limit = 20
+/// concurrency = 50
+/// endpoint_concurrency = 10
+/// login_file = "login.yaml"
+/// context = "staging"
+/// strict = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QcliConfig {
+    pub log_level: Option<log::Level>,
+    pub output: Option<OutputFormat>,
+    pub timeout: Option<u64>,
+    pub tls_verify: Option<bool>,
+    /// Relative paths are relative to the directory of the config file, as for the client
+    /// certificate and key
+    pub ca_bundle: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub rate_limit: Option<u32>,
+    pub concurrency: Option<usize>,
+    pub endpoint_concurrency: Option<usize>,
+    /// Relative paths are relative to the directory of the config file
+    pub login_file: Option<PathBuf>,
+    /// Context of the login file
+    pub context: Option<String>,
+    /// Reject the unknown fields of the yaml files
+    pub strict: Option<bool>,
+}
+
+impl QcliConfig {
+    /// Reads the config file given with --config or QCLI_CONFIG, which must exist, or the one
+    /// in the qcli config directory, if any.
+    pub fn load(path: Option<&str>) -> Result<QcliConfig, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match config_dir().map(|dir| dir.join("qcli.toml")) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(QcliConfig::default()),
+            },
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let mut config: QcliConfig = toml::from_str(&content)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+        if config.client_cert.is_some() != config.client_key.is_some() {
+            return Err(format!(
+                "Invalid config file {}: client_cert and client_key must be set together",
+                path.display()
+            )
+            .into());
+        }
+        if let Some(dir) = path.parent() {
+            config.login_file = config.login_file.map(|file| dir.join(file));
+            for file in [
+                &mut config.ca_bundle,
+                &mut config.client_cert,
+                &mut config.client_key,
+            ] {
+                *file = file
+                    .take()
+                    .map(|file| dir.join(file).to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Directory of the qcli config and login files: `$XDG_CONFIG_HOME/qcli`, or `~/.config/qcli`.
+pub fn config_dir() -> Option<PathBuf> {
+    let absolute = |var: &str| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+
+    absolute("XDG_CONFIG_HOME")
+        .or_else(|| absolute("HOME").map(|home| home.join(".config")))
+        .map(|dir| dir.join("qcli"))
+}
+
+/// Login file used when none is given: `.qcli/login.yaml` of the current directory if it
+/// exists, `login.yaml` of the qcli config directory otherwise.
+pub fn default_login_file() -> PathBuf {
+    let legacy = Path::new(LEGACY_LOGIN_FILE);
+    if legacy.exists() {
+        return legacy.to_path_buf();
+    }
+
+    config_dir()
+        .map(|dir| dir.join("login.yaml"))
+        .unwrap_or_else(|| legacy.to_path_buf())
+}
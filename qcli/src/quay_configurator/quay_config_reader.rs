@@ -1,98 +1,199 @@
-use super::organization_struct::{OrganizationYaml, QuayResponse};
+use super::credential_source::CredentialSource;
+use super::login_store::{self, NEW_PASSPHRASE_ENV, PASSPHRASE_ENV};
+use super::organization_drift::DriftReport;
+use super::organization_plan::{Change, DeleteSelection, OrganizationPlan};
+use super::organization_preflight::{Operations, OrganizationPreflight, TokenCheck};
+use super::organization_scheduler::OrganizationRun;
+use super::organization_schema;
+use super::organization_state::OWNERS_TEAM;
+use super::organization_struct::{api_base_url, OrganizationYaml, QuayRateLimiter};
+use super::retry_policy::RetryPolicy;
+use super::run_progress::RunProgress;
+use super::run_report::{OutputFormat, RunReport};
+use super::yaml_template;
 use crate::quay_configurator::organization_struct::{Actions, QuayFnArguments};
 use array_tool::vec::Uniq;
-use futures::future::join_all;
-use governor::clock::{QuantaClock, QuantaInstant};
-use governor::middleware::NoOpMiddleware;
-use governor::state::{InMemoryState, NotKeyed};
-use governor::{self, RateLimiter};
+use futures::stream::{self, StreamExt};
+use governor::{Quota, RateLimiter};
+use indicatif::MultiProgress;
 use log::{error, info, warn};
-use question::{Answer, Question};
-use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
-use std::io::{self, Write};
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Read, Write};
 use std::num::NonZeroU32;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+use std::time::Duration;
 use std::{fs::File, sync::Arc};
 use tokio::fs::{self, read_dir};
+use tokio::sync::{OnceCell, Semaphore};
 use tokio::time::Instant;
 
+/// Credential read from its source the first time it is needed, or the error reading it.
+type CachedCredential = OnceCell<Result<String, String>>;
+
 #[derive(Debug)]
 pub struct QuayXmlConfig {
     organization: Vec<OrganizationYaml>,
     directory: String,
-    governor: Arc<RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>>,
+    /// Rate limiter of each endpoint, created when the endpoint is first used
+    rate_limiters: Mutex<HashMap<String, Arc<QuayRateLimiter>>>,
+    /// Requests per second of the endpoints without a rate_limit in login config
+    requests_per_second: u32,
     log_level: log::Level,
     log_verbosity: u8,
     quay_login_configs: QuayLoginConfigs,
     timeout: u64,
-    tls_verify: bool,
+    /// TLS settings of the command line, the login config overrides them for its endpoints
+    tls: TlsOptions,
+    /// Max number of organizations processed at the same time, and max in flight requests
+    concurrency: usize,
+    /// Max in flight requests shared by every organization
+    global_limit: Arc<Semaphore>,
+    /// Max in flight requests of each endpoint, created when the endpoint is first used
+    endpoint_limits: Mutex<HashMap<String, Arc<Semaphore>>>,
+    endpoint_concurrency: usize,
+    retry_policy: RetryPolicy,
+    /// Pooled HTTP client for each endpoint, and certificate verification setting
+    clients: Mutex<HashMap<(String, bool), reqwest::Client>>,
+    /// Credentials read from their source, or the error reading them, set when first needed
+    credentials: Mutex<HashMap<CredentialSource, Arc<CachedCredential>>>,
+    /// Set by the first failed task when running with --fail-fast
+    fail_fast: Option<Arc<AtomicBool>>,
+    output: OutputFormat,
+    progress: Option<RunProgress>,
+    skip_preflight: bool,
+    /// Organizations excluded by the preflight checks, reported with the runs
+    preflight_failures: Vec<OrganizationRun>,
+    /// Login file with the Quay tokens and the mirror passwords
+    login_file: PathBuf,
+    /// Context of the login file selected on the command line
+    context: Option<String>,
+    /// Quay endpoints of the yaml files, mapped to the endpoints of the selected context
+    endpoint_map: BTreeMap<String, String>,
+    /// Template variables of the yaml files given on the command line
+    vars: BTreeMap<String, String>,
+    /// Reject the fields of the yaml files not in the organization schema
+    strict: bool,
+}
+
+/// Helper struct to pass the command line options to QuayXmlConfig.
+/// Useful for easily extend accepted options.
+#[derive(Debug, Clone)]
+pub struct QuayXmlConfigArguments {
+    /// Max requests per second sent to each Quay endpoint without a rate_limit in login config
+    pub requests_per_second: u32,
+    pub log_level: log::Level,
+    pub log_verbosity: u8,
+    /// Connection timeout in seconds
+    pub timeout: u64,
+    pub tls: TlsOptions,
+    /// Max number of in flight requests, and of organizations processed at the same time
+    pub concurrency: usize,
+    /// Max number of in flight requests for each Quay endpoint
+    pub endpoint_concurrency: usize,
+    pub retry_policy: RetryPolicy,
+    /// Stop starting new tasks after the first failure
+    pub fail_fast: bool,
+    /// Format of the run report
+    pub output: OutputFormat,
+    /// Live progress bars, `None` to log plain lines only
+    pub progress: Option<MultiProgress>,
+    /// Do not check tokens and permissions before running
+    pub skip_preflight: bool,
+    /// Login file with the Quay tokens and the mirror passwords
+    pub login_file: PathBuf,
+    /// Context of the login file, `None` to use its current_context
+    pub context: Option<String>,
+    /// Template variables of the yaml files, overriding their vars block
+    pub vars: BTreeMap<String, String>,
+    /// Reject the fields of the yaml files not in the organization schema, as misspelled ones
+    pub strict: bool,
 }
 
 impl QuayXmlConfig {
     pub fn new(
-        directory: &String,
-        req_per_seconds: u32,
-        log_level: log::Level,
-        log_verbosity: u8,
-        timeout: u64,
+        directory: &str,
+        arguments: QuayXmlConfigArguments,
         ignore_login_config: bool,
-        tls_verify: bool,
     ) -> Result<Self, Box<dyn Error>> {
-        let governor = Arc::new(governor::RateLimiter::direct(governor::Quota::per_minute(
-            NonZeroU32::new(req_per_seconds).unwrap(),
-        )));
-
-        if !ignore_login_config {
-            let quay_configs_file = File::open(".qcli/login.yaml")?;
-            match serde_yaml::from_reader(quay_configs_file) {
-                Ok(quay_login_configs) => {
-                    return Ok(Self {
-                        organization: vec![],
-                        directory: directory.clone(),
-                        governor,
-                        log_level,
-                        log_verbosity,
-                        timeout,
-                        tls_verify,
-                        quay_login_configs,
-                    });
-                }
-                Err(e) => return Err(Box::new(e)),
+        let QuayXmlConfigArguments {
+            requests_per_second,
+            log_level,
+            log_verbosity,
+            timeout,
+            tls,
+            concurrency,
+            endpoint_concurrency,
+            retry_policy,
+            fail_fast,
+            output,
+            progress,
+            skip_preflight,
+            login_file,
+            context,
+            vars,
+            strict,
+        } = arguments;
+
+        let (quay_login_configs, endpoint_map) = if !ignore_login_config {
+            let logins = Self::read_login_configs(&login_file)?;
+            if let Some(name) = logins.context_name(context.as_deref()) {
+                info!("Using context '{}' of {}", name, login_file.display());
             }
+            logins.select_context(context.as_deref())?
         } else {
             // Creating dummy configs
-            let quay_login_configs: QuayLoginConfigs = QuayLoginConfigs {
-                quay_endpoint_login: vec![],
-                mirror_repository: None,
-            };
-            return Ok(Self {
-                organization: vec![],
-                directory: directory.clone(),
-                governor,
-                log_level,
-                log_verbosity,
-                timeout,
-                tls_verify,
-                quay_login_configs,
-            });
-        }
+            (QuayLoginConfigs::default(), BTreeMap::new())
+        };
+
+        let concurrency = concurrency.max(1);
+        let endpoint_concurrency = endpoint_concurrency.max(1);
+
+        Ok(Self {
+            organization: vec![],
+            directory: directory.to_string(),
+            rate_limiters: Mutex::new(HashMap::new()),
+            requests_per_second,
+            log_level,
+            log_verbosity,
+            timeout,
+            tls,
+            quay_login_configs,
+            concurrency,
+            global_limit: Arc::new(Semaphore::new(concurrency)),
+            endpoint_limits: Mutex::new(HashMap::new()),
+            endpoint_concurrency,
+            retry_policy,
+            clients: Mutex::new(HashMap::new()),
+            credentials: Mutex::new(HashMap::new()),
+            fail_fast: fail_fast.then(|| Arc::new(AtomicBool::new(false))),
+            output,
+            progress: progress.map(RunProgress::new),
+            skip_preflight,
+            preflight_failures: Vec::new(),
+            login_file,
+            context,
+            endpoint_map,
+            vars,
+            strict,
+        })
     }
     pub async fn load_config(&mut self) -> Result<(), std::io::Error> {
         let mut files = read_dir(self.directory.to_owned()).await?;
 
         while let Some(f) = files.next_entry().await? {
             info!("Loading config from  {:?} ", f.file_name());
-            let f = File::open(f.path())?;
 
-            match serde_yaml::from_reader(f) {
-                Ok(scrape_config) => {
+            match self.read_organization(&f.path()) {
+                Ok((scrape_config, _)) => {
                     self.organization.push(scrape_config);
                 }
                 Err(e) => {
-                    error!("{:?}", e)
+                    error!("{}", e)
                 }
             }
         }
@@ -104,41 +205,89 @@ impl QuayXmlConfig {
         let tmp_organization = self.organization.clone();
 
         for org in &tmp_organization {
-            match &org.replicate_to {
-                Some(replicated_to) => {
-                    for endpoint in replicated_to {
-                        let mut new_org = org.clone();
-                        new_org.change_endpoint(endpoint.to_string());
-                        if !self.organization.contains(&new_org) {
-                            self.organization.push(new_org);
-                        } else {
-                            let str_error=format!("Endpoint replication '{}' already attached to the Quay organization '{}' with endpoint '{}'. Ignoring....",endpoint,new_org.quay_organization,new_org.quay_endpoint);
-                            warn!("{}", str_error);
-                        }
+            if let Some(replicated_to) = &org.replicate_to {
+                for endpoint in replicated_to {
+                    let mut new_org = org.clone();
+                    new_org.change_endpoint(endpoint.to_string());
+                    if !self.organization.contains(&new_org) {
+                        self.organization.push(new_org);
+                    } else {
+                        let str_error=format!("Endpoint replication '{}' already attached to the Quay organization '{}' with endpoint '{}'. Ignoring....",endpoint,new_org.quay_organization,new_org.quay_endpoint);
+                        warn!("{}", str_error);
                     }
                 }
-                None => {}
             }
         }
 
+        self.apply_endpoint_map();
+
         Ok(())
     }
 
+    /// Redirects the organizations to the endpoints of the selected context.
+    fn apply_endpoint_map(&mut self) {
+        let map = |endpoint: &String| {
+            self.endpoint_map
+                .get(endpoint)
+                .cloned()
+                .unwrap_or_else(|| endpoint.clone())
+        };
+
+        for org in &mut self.organization {
+            let endpoint = map(&org.quay_endpoint);
+            if endpoint != org.quay_endpoint {
+                info!(
+                    "Organization '{}': endpoint '{}' redirected to '{}'",
+                    org.quay_organization, org.quay_endpoint, endpoint
+                );
+                org.change_endpoint(endpoint);
+            }
+            if let Some(replicated_to) = &mut org.replicate_to {
+                for endpoint in replicated_to.iter_mut() {
+                    *endpoint = map(endpoint);
+                }
+            }
+        }
+    }
+
     pub async fn write_log(log_verbosity: u8, message: &str) {
         if log_verbosity >= 5 {
             info!("{}", message);
         }
     }
+    /// Reads an organization yaml file, rendering its templates first.
+    /// Returns also the rendered file, with the lines of the original one.
+    fn read_organization(&self, path: &Path) -> Result<(OrganizationYaml, String), Box<dyn Error>> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let content = yaml_template::render(&content, &self.vars)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut organization: OrganizationYaml = match self.strict {
+            true => organization_schema::from_str_strict(&content),
+            false => serde_yaml::from_str(&content).map_err(|e| e.into()),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        organization.strip_robot_prefixes();
+        Ok((organization, content))
+    }
+
+    /// Checks the yaml files, reporting every problem found with its file and line.
+    /// With `halt_on_error` the execution stops if a problem is found.
     pub async fn check_config(&self, halt_on_error: bool) -> Result<(), std::io::Error> {
+        let mut problems = 0;
         let mut files = read_dir(self.directory.to_owned()).await?;
         while let Some(f) = files.next_entry().await? {
-            let f2 = File::open(f.path())?;
-
-            let result: Result<OrganizationYaml, serde_yaml::Error> = serde_yaml::from_reader(f2);
+            let result = self.read_organization(&f.path());
             match result {
-                Ok(org) => {
+                Ok((org, content)) => {
                     info!("Config syntax of {:?} verified.  ", f.file_name());
 
+                    let org_problems = org.validate(&content);
+                    for problem in &org_problems {
+                        error!("{}: {}", f.path().display(), problem);
+                    }
+                    problems += org_problems.len();
+
                     let mut quay_endpoints: Vec<String> = Vec::new();
                     let mut quay_mirror_login = QuayMirrorLogin::default();
 
@@ -149,67 +298,58 @@ impl QuayXmlConfig {
 
                     quay_endpoints.push(org.quay_endpoint.clone());
 
-                    match org.replicate_to {
-                        Some(replicated_to) => {
-                            quay_endpoints.extend(replicated_to);
-                        }
-                        None => {}
+                    if let Some(replicated_to) = org.replicate_to {
+                        quay_endpoints.extend(replicated_to);
                     }
 
                     // Extract repositories mirror login informations
                     for repo in org.repositories {
-                        match repo.mirror_params {
-                            Some(mirror_params) => match mirror_params.ext_registry_username {
-                                Some(username) => {
-                                    let mirror_login = MirrorLogin {
-                                        organization: org.quay_organization.clone(),
-                                        repository: repo.name,
-                                        ext_registry_username: username,
-                                        ext_registry_password: "".to_string(),
-                                    };
-                                    quay_mirror_login.mirror_repository.push(mirror_login);
-                                }
-                                None => {}
-                            },
-
-                            None => {}
+                        if let Some(mirror_params) = repo.mirror_params {
+                            if let Some(username) = mirror_params.ext_registry_username {
+                                let mirror_login = MirrorLogin {
+                                    organization: org.quay_organization.clone(),
+                                    repository: repo.name,
+                                    ext_registry_username: username,
+                                    ext_registry_password: "".to_string(),
+                                    ext_registry_password_from: None,
+                                };
+                                quay_mirror_login.mirror_repository.push(mirror_login);
+                            }
                         }
                     }
 
                     let msg = &format!("Mirror repository wanted user {:?}", quay_mirror_login);
-                    Self::write_log(self.log_verbosity, &msg).await;
+                    Self::write_log(self.log_verbosity, msg).await;
 
                     //println!("WANTED {:?}", quay_mirror_login);
 
-                    match &self.quay_login_configs.mirror_repository {
-                        Some(actuals) => {
-                            for actual in actuals {
-                                for configured in &quay_mirror_login.mirror_repository {
-                                    if actual.ext_registry_username
-                                        == configured.ext_registry_username
-                                    {
-                                        if actual.repository == configured.repository {
-                                            if actual.organization == org.quay_organization {
-                                                if actual.ext_registry_password.len() > 0 {
-                                                    present_quay_mirror_login
-                                                        .mirror_repository
-                                                        .push(configured.clone());
-                                                }
-                                            }
-                                        }
-                                    }
+                    if let Some(actuals) = &self.quay_login_configs.mirror_repository {
+                        for actual in actuals {
+                            for configured in &quay_mirror_login.mirror_repository {
+                                if actual.ext_registry_username == configured.ext_registry_username
+                                    && actual.repository == configured.repository
+                                    && actual.organization == org.quay_organization
+                                    && actual.has_password()
+                                {
+                                    present_quay_mirror_login
+                                        .mirror_repository
+                                        .push(configured.clone());
                                 }
                             }
                         }
-                        None => {}
                     }
 
                     quay_endpoints = quay_endpoints.unique();
 
                     let msg = &format!("Found {} unique Quay endpoint(s)", quay_endpoints.len());
-                    Self::write_log(self.log_verbosity, &msg).await;
+                    Self::write_log(self.log_verbosity, msg).await;
 
-                    // Exit if halt_on_error==true.
+                    for endpoint in &quay_endpoints {
+                        if let Err(e) = api_base_url(endpoint) {
+                            error!("{:?}: {}", f.file_name(), e);
+                            problems += 1;
+                        }
+                    }
 
                     // Calculating vector difference
                     // if present_quay_mirror_login and quay_mirror_login Vector are different, there is no password in login.yaml for required repo.
@@ -228,553 +368,1152 @@ impl QuayXmlConfig {
 
                     let difference: HashSet<MirrorLogin> = (&s2 - &s1).iter().cloned().collect();
 
-                    if difference.len() > 0 {
-                        let msg = &format!("Missing mirror user password: {:?}. Check .qcli/login.yaml or run login subcommand.", difference);
+                    if !difference.is_empty() {
+                        let msg = &format!(
+                            "Missing mirror user password: {:?}. Check {} or run login subcommand.",
+                            difference,
+                            self.login_file.display()
+                        );
                         error!("{}", msg);
-                        if halt_on_error {
-                            std::process::exit(1);
-                        }
+                        problems += 1;
                     } else {
                         let msg = &format!("Missing mirror user password: {:?}", difference);
-                        Self::write_log(self.log_verbosity, &msg).await;
+                        Self::write_log(self.log_verbosity, msg).await;
                     }
                 } // OK
                 Err(e) => {
-                    error!("{:?}", e)
+                    error!("{}", e);
+                    problems += 1;
                 }
             }
         }
+
+        if problems > 0 {
+            error!(
+                "{} problem(s) found in the yaml files of {} directory.",
+                problems, self.directory
+            );
+            if halt_on_error {
+                std::process::exit(1);
+            }
+        }
         Ok(())
     }
 
-    pub async fn create_login(self) -> Result<(), Box<dyn Error>> {
+    /// Writes the login file with the tokens of the Quay endpoints and the mirror passwords
+    /// of the yaml files, asked on the terminal or read from stdin.
+    pub async fn create_login(mut self, arguments: LoginArguments) -> Result<(), Box<dyn Error>> {
         let mut quay_endpoints: Vec<String> = Vec::new();
         let mut quay_mirror_login = QuayMirrorLogin::default();
 
         // Extract organizations details from yaml
-        for org in self.organization {
+        for org in &self.organization {
             quay_endpoints.push(org.quay_endpoint.clone());
 
-            match org.replicate_to {
-                Some(replicated_to) => {
-                    quay_endpoints.extend(replicated_to);
-                }
-                None => {}
+            if let Some(replicated_to) = &org.replicate_to {
+                quay_endpoints.extend(replicated_to.iter().cloned());
             }
 
             // Extract repositories mirror login informations
-            for repo in org.repositories {
-                match repo.mirror_params {
-                    Some(mirror_params) => match mirror_params.ext_registry_username {
-                        Some(username) => {
-                            let mirror_login = MirrorLogin {
-                                organization: org.quay_organization.clone(),
-                                repository: repo.name,
-                                ext_registry_username: username,
-                                ext_registry_password: "".to_string(),
-                            };
-                            quay_mirror_login.mirror_repository.push(mirror_login);
-                        }
-                        None => {}
-                    },
-
-                    None => {}
+            for repo in &org.repositories {
+                if let Some(mirror_params) = &repo.mirror_params {
+                    if let Some(username) = &mirror_params.ext_registry_username {
+                        let mirror_login = MirrorLogin {
+                            organization: org.quay_organization.clone(),
+                            repository: repo.name.clone(),
+                            ext_registry_username: username.clone(),
+                            ext_registry_password: "".to_string(),
+                            ext_registry_password_from: None,
+                        };
+                        quay_mirror_login.mirror_repository.push(mirror_login);
+                    }
                 }
             } // for
         }
 
         let msg = &format!("Found {:?} Quay mirror login", quay_mirror_login);
-        Self::write_log(self.log_verbosity, &msg).await;
+        Self::write_log(self.log_verbosity, msg).await;
 
         quay_endpoints = quay_endpoints.unique();
 
         let msg = &format!("Found {} unique Quay endpoint(s)", quay_endpoints.len());
-        Self::write_log(self.log_verbosity, &msg).await;
+        Self::write_log(self.log_verbosity, msg).await;
+
+        // Checking if the login file directory exists and creating it if does not.
+        let login_file = self.login_file.clone();
+        let login_directory = login_file.parent().unwrap_or(Path::new(""));
 
-        // Checking if .qcli directory exists and creating it if does not.
+        if !login_directory.as_os_str().is_empty() && !login_directory.is_dir() {
+            let msg = &format!(
+                "{} directory does not exists. Creating...",
+                login_directory.display()
+            );
+            Self::write_log(self.log_verbosity, msg).await;
 
-        let login_directory = ".qcli";
-        let login_file = "login.yaml";
+            fs::create_dir_all(login_directory).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(login_directory, std::fs::Permissions::from_mode(0o700))
+                    .await?;
+            }
 
-        if !Path::new(login_directory).is_dir() {
-            let msg = &format!(".qcli directory does not exists. Creating...");
-            Self::write_log(self.log_verbosity, &msg).await;
+            let msg = &format!("{} directory created.", login_directory.display());
+            Self::write_log(self.log_verbosity, msg).await;
+        }
 
-            fs::create_dir(login_directory).await?;
+        let mut logins = QuayLoginConfigs::default();
 
-            let msg = &format!(".qcli directory created.");
-            Self::write_log(self.log_verbosity, &msg).await;
-        } else {
-            let msg = &format!(".qcli directory exists.");
-            Self::write_log(self.log_verbosity, &msg).await;
-        }
-
-        let relative_login_file_path=format!("{}/{}", login_directory, login_file);
-        if std::path::Path::new(&relative_login_file_path).exists() {
-            match Question::new(".qcli/login.yaml exists: Do you want to recreate it?")
-                .yes_no()
-                .until_acceptable()
-                .default(Answer::YES)
-                .show_defaults()
-                .ask() {
-                    Some(answer) => {
-                        match answer {
-                            Answer::RESPONSE(_) => {},
-                            Answer::NO => {},
-                            Answer::YES => {
-
-                                fs::remove_file(&relative_login_file_path).await?
-                            },
-                            
+        // With a context, only its tokens are recreated: the rest of the file is kept
+        if login_file.exists() && (arguments.merge || self.context.is_some()) {
+            logins = Self::read_login_configs(&login_file)?;
+        }
+        let context = logins.context_name(self.context.as_deref());
+
+        let existing = match &context {
+            Some(name) => logins
+                .contexts
+                .iter()
+                .any(|c| &c.name == name)
+                .then(|| format!("Context '{}' of {}", name, login_file.display())),
+            None => login_file
+                .exists()
+                .then(|| login_file.display().to_string()),
+        };
+        match existing {
+            Some(_) if arguments.merge => {}
+            Some(existing) => {
+                if !arguments.non_interactive {
+                    match ask_yes_no(
+                        &format!("{} exists: Do you want to recreate it?", existing),
+                        true,
+                    )? {
+                        Some(true) => {}
+                        Some(false) => {
+                            info!("{} not changed. Run qcli login --merge to add missing endpoints and mirror passwords.", existing);
+                            return Ok(());
+                        }
+                        None => {
+                            return Err(format!("{} exists and no answer was given to recreate it. Run qcli login --merge, or remove the file.", existing).into());
                         }
-                    },
-                    None => todo!(),
+                    }
                 }
+                // The endpoint remapping of a context is kept
+                let target = logins.logins_mut(context.as_deref());
+                target.quay_endpoint_login.clear();
+                target.mirror_repository = None;
+            }
+            None => warn!("{} does not exits. Creating...", login_file.display()),
+        }
+        logins.logins_mut(context.as_deref());
+
+        // Tokens are asked for the endpoints of the context
+        if let Some(name) = &context {
+            self.endpoint_map = logins.get_context(name)?.endpoints.clone();
+            self.apply_endpoint_map();
+            quay_endpoints = quay_endpoints
+                .iter()
+                .map(|e| self.endpoint_map.get(e).unwrap_or(e).clone())
+                .collect::<Vec<String>>()
+                .unique();
         }
-        // To do - check path for windows
-        if !std::path::Path::new(&relative_login_file_path).exists() {
-            warn!("{} does not exits. Creating...", login_file);
-
-            let mut logins = QuayLoginConfigs::default();
 
-            for q in quay_endpoints {
-                print!("Please insert token for {}: ", q);
-                io::stdout().flush()?;
-                let mut token = String::new();
-                io::stdin().read_line(&mut token)?;
+        if arguments.non_interactive {
+            let mut document = String::new();
+            io::stdin().read_to_string(&mut document)?;
+            let provided: QuayLoginConfigs = serde_yaml::from_str(&document)
+                .map_err(|e| format!("Invalid login document on stdin: {}", e))?;
+            logins.logins_mut(context.as_deref()).merge(provided);
+            logins.validate_contexts()?;
+        }
 
-                let endpoint = QuayEndopoint {
-                    quay_endpoint: q,
-                    quay_token: token.trim().to_string(),
-                };
+        let mut missing = vec![];
 
-                logins.quay_endpoint_login.push(endpoint);
+        for q in quay_endpoints {
+            let (selected, _) = logins.select_context(context.as_deref())?;
+            if selected.get_quay_endpoint_login(&q).is_some() {
+                continue;
+            }
+            if arguments.non_interactive {
+                missing.push(format!("token for {}", q));
+                continue;
             }
 
-            let mut tmp_quay_mirror_login = QuayMirrorLogin::default();
+            let token = read_secret(&format!("Please insert token for {}: ", q))?;
+
+            logins
+                .logins_mut(context.as_deref())
+                .merge(QuayLoginConfigs {
+                    quay_endpoint_login: vec![QuayEndopoint {
+                        quay_endpoint: q,
+                        quay_token: token,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                });
+        }
 
-            for mirror in quay_mirror_login.mirror_repository {
-                print!(
-                    "Please insert password for user '{}', repository '{}' of organization '{}' :",
+        for mirror in quay_mirror_login.mirror_repository {
+            let (selected, _) = logins.select_context(context.as_deref())?;
+            if selected.get_mirror_login(&mirror).is_some() {
+                continue;
+            }
+            if arguments.non_interactive {
+                missing.push(format!(
+                    "password for user '{}', repository '{}' of organization '{}'",
                     mirror.ext_registry_username, mirror.repository, mirror.organization
-                );
-                io::stdout().flush()?;
-                let mut token = String::new();
-                io::stdin().read_line(&mut token)?;
-
-                let single_mirror_login = MirrorLogin {
-                    organization: mirror.organization,
-                    repository: mirror.repository,
-                    ext_registry_username: mirror.ext_registry_username,
-                    ext_registry_password: token.trim().to_string(),
-                };
-
-                tmp_quay_mirror_login
-                    .mirror_repository
-                    .push(single_mirror_login);
-            } // for
+                ));
+                continue;
+            }
 
-            let f = std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(true)
-                .open(&relative_login_file_path)?;
+            let password = read_secret(&format!(
+                "Please insert password for user '{}', repository '{}' of organization '{}' :",
+                mirror.ext_registry_username, mirror.repository, mirror.organization
+            ))?;
+
+            logins
+                .logins_mut(context.as_deref())
+                .merge(QuayLoginConfigs {
+                    mirror_repository: Some(vec![MirrorLogin {
+                        ext_registry_password: password,
+                        ..mirror
+                    }]),
+                    ..Default::default()
+                });
+        }
 
-            logins.mirror_repository = Some(tmp_quay_mirror_login.mirror_repository);
+        if !missing.is_empty() {
+            return Err(format!("Missing in the login document: {}", missing.join(", ")).into());
+        }
 
-            serde_yaml::to_writer(f, &logins)?;
+        // Check the tokens before saving them
+        self.quay_login_configs = logins.select_context(context.as_deref())?.0;
+        let rejected: Vec<String> = self
+            .preflight_all(Operations::Reconcile, None)
+            .await
+            .into_iter()
+            .filter(|preflight| !preflight.errors.is_empty())
+            .map(|preflight| preflight.quay_endpoint)
+            .collect::<Vec<String>>()
+            .unique();
+        if !rejected.is_empty() {
+            return Err(format!(
+                "Tokens not valid for {}. Nothing saved: run qcli login again, or add --skip-preflight to save them anyway.",
+                rejected.join(", ")
+            )
+            .into());
         }
 
+        let passphrase =
+            login_store::read_passphrase(PASSPHRASE_ENV, "Login store passphrase: ", true)?;
+        login_store::save(&login_file, &serde_yaml::to_string(&logins)?, &passphrase)?;
+        info!("{} encrypted and saved.", login_file.display());
+
         Ok(())
     }
 
-    pub fn get_organizations(&self) -> &Vec<OrganizationYaml> {
-        &self.organization
+    /// Reads the login file, decrypting it if encrypted.
+    fn read_login_configs(login_file: &Path) -> Result<QuayLoginConfigs, Box<dyn Error>> {
+        Ok(Self::load_login_configs(login_file)?.0)
     }
 
-    pub fn get_cloned_governor(
+    /// Reads the login file, returning also the passphrase if it is encrypted.
+    fn load_login_configs(
+        login_file: &Path,
+    ) -> Result<(QuayLoginConfigs, Option<String>), Box<dyn Error>> {
+        let (login_yaml, passphrase) = login_store::load(login_file)
+            .map_err(|e| format!("{}: {}", login_file.display(), e))?;
+        let logins: QuayLoginConfigs = serde_yaml::from_str(&login_yaml)
+            .map_err(|e| format!("{}: {}", login_file.display(), e))?;
+        logins
+            .validate_contexts()
+            .map_err(|e| format!("{}: {}", login_file.display(), e))?;
+        if passphrase.is_none() && logins.has_secrets() {
+            warn!(
+                "{} stores tokens in plain text. Run qcli login --rekey to encrypt it.",
+                login_file.display()
+            );
+        }
+        Ok((logins, passphrase))
+    }
+
+    /// Writes the login file back, encrypted with the same passphrase if it was encrypted.
+    fn write_login_configs(
         &self,
-    ) -> Arc<RateLimiter<NotKeyed, InMemoryState, QuantaClock, NoOpMiddleware<QuantaInstant>>> {
-        self.governor.clone()
+        logins: &QuayLoginConfigs,
+        passphrase: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let login_yaml = serde_yaml::to_string(logins)?;
+        match passphrase {
+            Some(passphrase) => login_store::save(&self.login_file, &login_yaml, passphrase)?,
+            None => login_store::write_private(&self.login_file, &login_yaml)?,
+        }
+        Ok(())
     }
 
-    fn print_result(&self, _description: String, result: Result<QuayResponse, Box<dyn Error>>) {
-        match result {
-            Ok(r) => {
-                let mut corrected_description = String::new();
+    /// Prints the login file, with tokens and passwords masked.
+    pub fn show_login(&self) -> Result<(), Box<dyn Error>> {
+        let mut logins = Self::read_login_configs(&self.login_file)?;
+        logins.mask_secrets();
 
-                if r.status_code == StatusCode::NO_CONTENT {
-                    // 204 from success delete organization
-                    corrected_description.insert_str(0, "No Content success");
-                } else {
-                    corrected_description.insert_str(0, r.description.as_str());
-                }
-                if self.log_verbosity >= 5 {
-                    info!("{:?}", r);
-                }
-                //println!("------------------------");
-                //println!("{} {}", description, corrected_description);
-                //println!("Status code: {}", r.status_code);
-                //println!("Message: {}", r.response);
-            }
-            Err(e) => {
-                error!("Error: {}", e);
-                panic!(
-                    "{}",
-                    format!("Can not continue. Please check network connectivity")
-                );
+        print!("{}", serde_yaml::to_string(&logins)?);
+        Ok(())
+    }
+
+    /// Prints the contexts of the login file with their endpoint remapping.
+    /// The current context is marked with `*`.
+    pub fn list_contexts(&self) -> Result<(), Box<dyn Error>> {
+        let logins = Self::read_login_configs(&self.login_file)?;
+        let current = logins.context_name(None);
+
+        let marker = |name: Option<&String>| match current.as_ref() == name {
+            true => "*",
+            false => " ",
+        };
+        println!("{} {}", marker(None), DEFAULT_CONTEXT);
+        for context in &logins.contexts {
+            println!("{} {}", marker(Some(&context.name)), context.name);
+            for (from, to) in &context.endpoints {
+                println!("    {} -> {}", from, to);
             }
         }
+        Ok(())
     }
 
-    pub async fn delete_all(&self) -> Result<(), Box<dyn Error>> {
-        let mut handles_delete_organization = Vec::new();
-        let orgs = self.get_organizations();
+    /// Sets the context used when --context is not set. `default` selects the top level logins.
+    pub fn use_context(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let (mut logins, passphrase) = Self::load_login_configs(&self.login_file)?;
+        if name != DEFAULT_CONTEXT {
+            logins.get_context(name)?;
+        }
 
-        for org in orgs {
-            info!("Processing organization: {}", org.quay_organization);
+        logins.current_context = (name != DEFAULT_CONTEXT).then(|| name.to_string());
+        self.write_login_configs(&logins, passphrase.as_deref())?;
+        info!("Switched to context '{}'.", name);
+        Ok(())
+    }
 
-            let token: String;
+    /// Adds the context if missing, and redirects the endpoints of the yaml files to the ones
+    /// of the context. Tokens of the context are set with `qcli --context <name> login`.
+    pub fn set_context(
+        &self,
+        name: &str,
+        endpoints: &[(String, String)],
+    ) -> Result<(), Box<dyn Error>> {
+        if name == DEFAULT_CONTEXT {
+            return Err(format!("Context '{}' can not be changed", DEFAULT_CONTEXT).into());
+        }
+        let (mut logins, passphrase) = Self::load_login_configs(&self.login_file)?;
 
-            if let Some(t) = self
-                .quay_login_configs
-                .get_token_from_quay_endopoint(org.get_quay_endpoint())
-            {
-                token = t
-            } else {
-                let err_str = format!("No token found for {} Quay endpoint. Please run 'qcli login. Ignoring this Quay organization.",org.get_quay_endpoint());
-                error!("{}", err_str);
-                continue;
-            }
+        logins.logins_mut(Some(name));
+        if let Some(context) = logins.contexts.iter_mut().find(|c| c.name == name) {
+            context.endpoints.extend(endpoints.iter().cloned());
+        }
 
-            let quay_fn_arguments = QuayFnArguments {
-                token,
-                governor: self.get_cloned_governor(),
-                log_level: self.log_level,
-                log_verbosity: self.log_verbosity,
-                timeout: self.timeout,
-                tls_verify: self.tls_verify,
-                mirror_login: None,
-            };
+        self.write_login_configs(&logins, passphrase.as_deref())?;
+        info!("Context '{}' saved.", name);
+        Ok(())
+    }
 
-            handles_delete_organization.push(org.delete_organization(quay_fn_arguments));
-        }
-        let now = Instant::now();
+    /// Encrypts the login file with a new passphrase. Plain text login files are encrypted.
+    pub fn rekey_login(&self) -> Result<(), Box<dyn Error>> {
+        let (login_yaml, _) = login_store::load(&self.login_file)
+            .map_err(|e| format!("{}: {}", self.login_file.display(), e))?;
+        // Never write back something that is not a login file
+        let _: QuayLoginConfigs = serde_yaml::from_str(&login_yaml)?;
 
+        let passphrase =
+            login_store::read_passphrase(NEW_PASSPHRASE_ENV, "New login store passphrase: ", true)?;
+        login_store::save(&self.login_file, &login_yaml, &passphrase)?;
         info!(
-            "Deleting {} organization...",
-            handles_delete_organization.len()
+            "{} encrypted with the new passphrase.",
+            self.login_file.display()
         );
-        let results = join_all(handles_delete_organization);
+        Ok(())
+    }
 
-        for result in results.await {
-            self.print_result("Organization ->".to_string(), result);
-        }
+    pub fn get_organizations(&self) -> &Vec<OrganizationYaml> {
+        &self.organization
+    }
 
-        info!(
-            "Organizations deleted in {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
+    /// Returns the rate limiter of the Quay endpoint, shared by every request to the endpoint.
+    ///
+    /// Each endpoint has its own quota, so replicas do not slow down the primary endpoint.
+    fn get_rate_limiter(&self, endpoint: &str) -> Arc<QuayRateLimiter> {
+        let rate_limit = self
+            .quay_login_configs
+            .quay_endpoint_login
+            .iter()
+            .find(|e| e.quay_endpoint == endpoint)
+            .and_then(|e| e.rate_limit)
+            .unwrap_or(self.requests_per_second);
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_insert_with(|| Self::build_rate_limiter(rate_limit))
+            .clone()
+    }
 
-        Ok(())
+    /// Returns the limit of in flight requests of the Quay endpoint.
+    fn get_endpoint_limit(&self, endpoint: &str) -> Arc<Semaphore> {
+        self.endpoint_limits
+            .lock()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.endpoint_concurrency)))
+            .clone()
     }
 
-    pub async fn create_all(&self) -> Result<(), Box<dyn Error>> {
-        let mut handles_all_organizations = Vec::new();
-        // let mut handles_delete_organization = Vec::new();
-        let mut handles_all_robots = Vec::new();
-        let mut handles_all_teams = Vec::new();
-        let mut handles_all_repositories = Vec::new();
-        let mut handles_all_repositories_permissions = Vec::new();
-        let mut handles_all_team_members = Vec::new();
-        let mut handles_all_extra_user_permissions = Vec::new();
-        let mut handles_all_extra_team_permissions = Vec::new();
-        let mut handles_all_mirror_configurations = Vec::new();
-
-        let orgs = self.get_organizations();
-
-        for org in orgs {
-            info!(
-                "Processing config for organization: {}",
-                org.quay_organization
+    /// Builds a rate limiter allowing `requests_per_second` requests per second.
+    fn build_rate_limiter(requests_per_second: u32) -> Arc<QuayRateLimiter> {
+        let requests_per_second = NonZeroU32::new(requests_per_second).unwrap_or(NonZeroU32::MIN);
+        Arc::new(RateLimiter::direct(Quota::per_second(requests_per_second)))
+    }
+
+    /// TLS settings of the endpoint: the login config ones, or the command line ones.
+    ///
+    /// Certificates are verified unless disabled by --tls-verify or by the organization's
+    /// quay_validate_certs, the command line wins over the yaml files.
+    fn get_tls_options(&self, org: &OrganizationYaml) -> TlsOptions {
+        let login = self
+            .quay_login_configs
+            .quay_endpoint_login
+            .iter()
+            .find(|e| e.quay_endpoint == org.quay_endpoint);
+
+        TlsOptions {
+            verify: self.tls.verify.or(Some(org.validate_certs())),
+            ca_file: login
+                .and_then(|e| e.ca_file.clone())
+                .or_else(|| self.tls.ca_file.clone()),
+            client_cert: login
+                .and_then(|e| e.client_cert.clone())
+                .or_else(|| self.tls.client_cert.clone()),
+            client_key: login
+                .and_then(|e| e.client_key.clone())
+                .or_else(|| self.tls.client_key.clone()),
+        }
+    }
+
+    /// Returns the HTTP client of the organization's endpoint, building it at the first use.
+    fn get_client(&self, org: &OrganizationYaml) -> Result<reqwest::Client, Box<dyn Error>> {
+        let tls = self.get_tls_options(org);
+        let key = (org.get_quay_endpoint(), tls.verify.unwrap_or(true));
+
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        if !key.1 {
+            warn!(
+                "TLS certificate verification disabled for {} Quay endpoint",
+                key.0
             );
+        }
+        let client = Self::build_client(self.timeout, &tls)?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
 
-            let token: String;
+    /// Builds the HTTP client shared by every request sent to a Quay endpoint.
+    /// Connections are kept alive and HTTP/2 is used when Quay supports it.
+    fn build_client(timeout: u64, tls: &TlsOptions) -> Result<reqwest::Client, Box<dyn Error>> {
+        let mut builder = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!tls.verify.unwrap_or(true))
+            .timeout(Duration::from_secs(timeout))
+            .connect_timeout(Duration::from_secs(timeout))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60))
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .http2_keep_alive_while_idle(true);
+
+        if let Some(ca_file) = &tls.ca_file {
+            let pem = std::fs::read(ca_file)
+                .map_err(|e| format!("Unable to read CA bundle {}: {}", ca_file, e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid CA bundle {}: {}", ca_file, e))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
 
-            if let Some(t) = self
-                .quay_login_configs
-                .get_token_from_quay_endopoint(org.get_quay_endpoint())
-            {
-                token = t
-            } else {
-                let err_str = format!("No token found for {} Quay endpoint. Please run 'qcli login. Ignoring this Quay organization.",org.get_quay_endpoint());
-                error!("{}", err_str);
-                continue;
+        match (&tls.client_cert, &tls.client_key) {
+            (Some(cert_file), Some(key_file)) => {
+                let cert = std::fs::read(cert_file).map_err(|e| {
+                    format!("Unable to read client certificate {}: {}", cert_file, e)
+                })?;
+                let key = std::fs::read(key_file)
+                    .map_err(|e| format!("Unable to read client key {}: {}", key_file, e))?;
+                let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key).map_err(|e| {
+                    format!(
+                        "Invalid client certificate {} or key {}: {}",
+                        cert_file, key_file, e
+                    )
+                })?;
+                builder = builder.identity(identity);
             }
+            (None, None) => {}
+            _ => return Err("Client certificate and client key must be set together".into()),
+        }
 
-            let mut tmp_mirror_login = vec![];
+        Ok(builder.build()?)
+    }
 
-            match &self.quay_login_configs.mirror_repository {
-                Some(login_vec) => {
-                    for l in login_vec {
-                        if l.organization == org.quay_organization {
-                            tmp_mirror_login.push(l.clone());
-                        }
-                    }
-                }
-                None => {}
-            };
+    /// Returns the credential read from its source if set, otherwise the credential saved
+    /// in the login file. Sources are read once, the first time they are needed.
+    async fn resolve_credential(
+        &self,
+        saved: &str,
+        source: Option<&CredentialSource>,
+    ) -> Result<String, Box<dyn Error>> {
+        let source = match source {
+            Some(source) => source,
+            None => return Ok(saved.to_string()),
+        };
+
+        // The lock is released before reading, concurrent readers of a source wait for the first one
+        let credential = self
+            .credentials
+            .lock()
+            .unwrap()
+            .entry(source.clone())
+            .or_default()
+            .clone();
+        Ok(credential
+            .get_or_init(|| async { source.resolve().await.map_err(|e| e.to_string()) })
+            .await
+            .clone()?)
+    }
 
-            let quay_fn_arguments = QuayFnArguments {
-                token,
-                governor: self.get_cloned_governor(),
-                log_level: self.log_level,
-                log_verbosity: self.log_verbosity,
-                timeout: self.timeout,
-                tls_verify: self.tls_verify,
-                mirror_login: Some(tmp_mirror_login),
-            };
+    /// Checks that the tokens are valid and have the permissions needed by the operations,
+    /// sending only read requests. `organization` restricts the checks to the organizations with that name.
+    ///
+    /// Organizations failing the checks are reported and skipped by the next runs, unless the
+    /// operations only read them.
+    pub async fn preflight_all(
+        &mut self,
+        operations: Operations,
+        organization: Option<&String>,
+    ) -> Vec<OrganizationPreflight> {
+        if self.skip_preflight {
+            return Vec::new();
+        }
 
-            handles_all_organizations.push(org.create_organization(quay_fn_arguments.clone()));
+        let orgs: Vec<&OrganizationYaml> = self
+            .get_organizations()
+            .iter()
+            .filter(|org| organization.is_none_or(|o| &org.quay_organization == o))
+            .collect();
 
-            for robot in &org.robots {
-                handles_all_robots.push(org.create_robot(robot, quay_fn_arguments.clone()));
+        let mut tokens: HashMap<String, TokenCheck> = HashMap::new();
+        for org in &orgs {
+            if tokens.contains_key(&org.quay_endpoint) {
+                continue;
             }
-            for team in &org.teams {
-                handles_all_teams.push(org.create_team(team, quay_fn_arguments.clone()));
-
-                for member in &team.members.users {
-                    handles_all_team_members.push(org.add_user_to_team(
-                        &team.name,
-                        &member,
-                        quay_fn_arguments.clone(),
-                    ))
+            // The first organization of the endpoint, for its TLS settings
+            if let Some(quay_fn_arguments) = self.get_quay_fn_arguments(org).await {
+                let token = org.check_token(quay_fn_arguments).await;
+                match (&token.error, &token.username) {
+                    (Some(e), _) => error!("Quay endpoint '{}': {}", org.quay_endpoint, e),
+                    (None, Some(username)) => {
+                        info!("Quay endpoint '{}': token of user '{}'", org.quay_endpoint, username)
+                    }
+                    (None, None) => warn!(
+                        "Quay endpoint '{}': token valid, but it can not read its user (user:read scope missing)",
+                        org.quay_endpoint
+                    ),
                 }
+                tokens.insert(org.quay_endpoint.clone(), token);
+            }
+        }
 
-                for member in &team.members.robots {
-                    handles_all_team_members.push(org.add_robot_to_team(
-                        &team.name,
-                        &member,
-                        quay_fn_arguments.clone(),
-                    ))
+        let tokens = &tokens;
+        let config = &*self;
+        let preflights: Vec<OrganizationPreflight> = stream::iter(orgs)
+            .filter_map(|org| async move {
+                match (
+                    tokens.get(&org.quay_endpoint),
+                    config.get_quay_fn_arguments(org).await,
+                ) {
+                    (Some(token), Some(quay_fn_arguments)) => {
+                        Some(org.preflight(token, operations, quay_fn_arguments))
+                    }
+                    _ => None,
                 }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut failures = Vec::new();
+        for preflight in &preflights {
+            let name = format!(
+                "Organization '{}' on '{}'",
+                preflight.quay_organization, preflight.quay_endpoint
+            );
+            for e in &preflight.errors {
+                error!("{}: {}", name, e);
             }
-
-            for repository in &org.repositories {
-                handles_all_repositories
-                    .push(org.create_repository(repository, quay_fn_arguments.clone()));
-                handles_all_extra_user_permissions.push(
-                    org.get_user_permission_from_repository(&repository, quay_fn_arguments.clone()),
-                );
-                handles_all_extra_team_permissions.push(
-                    org.get_team_permission_from_repository(&repository, quay_fn_arguments.clone()),
+            if !preflight.missing.is_empty() {
+                let msg = format!(
+                    "{}: the token can not run the operations of the yaml file, missing {}",
+                    name,
+                    preflight.missing.join(", ")
                 );
+                match operations {
+                    Operations::Read => warn!("{}", msg),
+                    _ => error!("{}", msg),
+                }
+            }
+            if preflight.errors.is_empty() && preflight.missing.is_empty() {
+                let user = preflight.username.as_deref().unwrap_or("unknown user");
+                match preflight.exists {
+                    true => info!("{}: the token of '{}' can administer it", name, user),
+                    false => info!("{}: not found, '{}' will create it", name, user),
+                }
+            }
 
-                handles_all_mirror_configurations
-                    .push(org.create_repository_mirror(&repository, quay_fn_arguments.clone()));
-
-                if let Some(permissions) = &repository.permissions {
-                    for robot in &permissions.robots {
-                        handles_all_repositories_permissions.push(
-                            org.grant_robot_permission_to_repository(
-                                &repository.name,
-                                &robot,
-                                quay_fn_arguments.clone(),
-                            ),
-                        )
-                    }
+            if !preflight.is_ok(operations) {
+                let errors = preflight
+                    .errors
+                    .iter()
+                    .map(|e| format!("Preflight: {}", e))
+                    .chain(
+                        preflight
+                            .missing
+                            .iter()
+                            .map(|scope| format!("Preflight: missing {}", scope)),
+                    )
+                    .collect();
+                let org =
+                    OrganizationYaml::new(&preflight.quay_endpoint, &preflight.quay_organization);
+                failures.push(OrganizationRun::failed(&org, errors));
+            }
+        }
 
-                    for team in &permissions.teams {
-                        for t in team {
-                            handles_all_repositories_permissions.push(
-                                org.grant_team_permission_to_repository(
-                                    &repository.name,
-                                    &t,
-                                    quay_fn_arguments.clone(),
-                                ),
-                            )
-                        }
-                    }
-                    for user in &permissions.users {
-                        handles_all_repositories_permissions.push(
-                            org.grant_user_permission_to_repository(
-                                &repository.name,
-                                &user,
-                                quay_fn_arguments.clone(),
-                            ),
+        if operations != Operations::Read {
+            self.preflight_failures = failures;
+        }
+        preflights
+    }
+
+    /// Builds the function arguments for the organization's Quay endpoint.
+    /// Returns `None` if there is no token for the endpoint, or the organization failed the preflight checks.
+    async fn get_quay_fn_arguments(&self, org: &OrganizationYaml) -> Option<QuayFnArguments> {
+        if self.preflight_failures.iter().any(|run| {
+            run.quay_endpoint == org.quay_endpoint && run.quay_organization == org.quay_organization
+        }) {
+            return None;
+        }
+
+        let login = self
+            .quay_login_configs
+            .get_quay_endpoint_login(&org.get_quay_endpoint());
+        let token = match login {
+            Some(login) => Some(
+                self.resolve_credential(&login.quay_token, login.quay_token_from.as_ref())
+                    .await,
+            ),
+            None => None,
+        };
+        let token = match token {
+            Some(Ok(token)) => token,
+            Some(Err(e)) => {
+                error!(
+                    "Unable to read the token of {} Quay endpoint from {}. Ignoring this Quay organization.",
+                    org.get_quay_endpoint(),
+                    e
+                );
+                return None;
+            }
+            None => {
+                let err_str = format!("No token found for {} Quay endpoint. Please run 'qcli login. Ignoring this Quay organization.",org.get_quay_endpoint());
+                error!("{}", err_str);
+                return None;
+            }
+        };
+
+        let mut tmp_mirror_login = vec![];
+
+        if let Some(login_vec) = &self.quay_login_configs.mirror_repository {
+            for l in login_vec {
+                if l.organization == org.quay_organization {
+                    let mut mirror_login = l.clone();
+                    match self
+                        .resolve_credential(
+                            &l.ext_registry_password,
+                            l.ext_registry_password_from.as_ref(),
                         )
+                        .await
+                    {
+                        Ok(password) => mirror_login.ext_registry_password = password,
+                        Err(e) => {
+                            error!(
+                                "Unable to read the mirror password of user '{}', repository '{}' from {}. Ignoring this Quay organization.",
+                                l.ext_registry_username, l.repository, e
+                            );
+                            return None;
+                        }
                     }
+                    tmp_mirror_login.push(mirror_login);
                 }
             }
+        };
+
+        let client = match self.get_client(org) {
+            Ok(client) => client,
+            Err(e) => {
+                error!(
+                    "Unable to build HTTP client for {} Quay endpoint: {}",
+                    org.get_quay_endpoint(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        Some(QuayFnArguments {
+            token,
+            client,
+            governor: self.get_rate_limiter(&org.get_quay_endpoint()),
+            log_level: self.log_level,
+            log_verbosity: self.log_verbosity,
+            mirror_login: Some(tmp_mirror_login),
+            global_limit: self.global_limit.clone(),
+            endpoint_limit: self.get_endpoint_limit(&org.get_quay_endpoint()),
+            concurrency: self.concurrency.min(self.endpoint_concurrency),
+            retry_policy: self.retry_policy,
+            fail_fast: self.fail_fast.clone(),
+            progress: self.progress.clone(),
+            sent_requests: self
+                .progress
+                .as_ref()
+                .map(|progress| progress.request_counter(&org.get_quay_endpoint())),
+        })
+    }
+
+    /// Deletes the organizations, or only the selected objects if the selection is not empty.
+    /// `organization` restricts the deletion to the organizations with that name.
+    pub async fn delete_all(
+        &self,
+        organization: Option<&String>,
+        selection: &DeleteSelection,
+    ) -> Result<(), Box<dyn Error>> {
+        let orgs: Vec<&OrganizationYaml> = self
+            .get_organizations()
+            .iter()
+            .filter(|org| organization.is_none_or(|o| &org.quay_organization == o))
+            .collect();
+
+        if let Some(organization) = organization {
+            if orgs.is_empty() {
+                return Err(format!(
+                    "Organization '{}' not found in {} directory",
+                    organization, self.directory
+                )
+                .into());
+            }
         }
 
-        let total_requestes = handles_all_organizations.len()
-            + handles_all_robots.len()
-            + handles_all_teams.len()
-            + handles_all_repositories.len()
-            + (handles_all_repositories_permissions.len() * 2)
-            + handles_all_team_members.len()
-            + handles_all_extra_user_permissions.len()
-            + handles_all_extra_team_permissions.len()
-            + (handles_all_mirror_configurations.len() * 3);
+        let plans: Vec<OrganizationPlan> = orgs
+            .into_iter()
+            .map(|org| OrganizationPlan::deletions(org, selection))
+            .collect();
 
-        info!("TOTAL REQUESTS : {}", total_requestes);
+        self.apply_all(&plans).await
+    }
 
-        // Create organization
+    /// Creates every organization, each one as an independent graph of tasks.
+    pub async fn create_all(&self) -> Result<(), Box<dyn Error>> {
         info!(
-            "Creating {} organization...",
-            handles_all_organizations.len()
+            "Creating {} organizations...",
+            self.get_organizations().len()
         );
 
-        let results = join_all(handles_all_organizations);
-
         let now = Instant::now();
-        for result in results.await {
-            self.print_result("Organization ->".to_string(), result);
+        let runs: Vec<OrganizationRun> = stream::iter(self.get_organizations())
+            .filter_map(|org| async move {
+                info!(
+                    "Processing config for organization: {}",
+                    org.quay_organization
+                );
+                self.get_quay_fn_arguments(org)
+                    .await
+                    .map(|quay_fn_arguments| org.create_and_prune(quay_fn_arguments))
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        self.print_runs(&runs, now)
+    }
+
+    /// Prints the run report, with the executed tasks grouped by organization and phase.
+    /// Returns an error if any task failed.
+    fn print_runs(&self, runs: &[OrganizationRun], now: Instant) -> Result<(), Box<dyn Error>> {
+        if let Some(progress) = &self.progress {
+            progress.finish();
         }
 
+        let runs = &[runs, &self.preflight_failures].concat();
+        let report = RunReport::new(runs);
+        println!("{}", report.render(self.output)?);
+
         info!(
-            "Organizations created in  {} seconds.",
+            "{} organizations processed in {} seconds.",
+            runs.len(),
             now.elapsed().as_secs_f32()
         );
 
-        // Create robots
-        info!("Creating {} robots...", handles_all_robots.len());
+        let failed = report.failed_organizations();
+        if failed > 0 {
+            return Err(format!("{} organizations have failed tasks", failed).into());
+        }
 
-        let now = Instant::now();
-        let results = join_all(handles_all_robots);
+        Ok(())
+    }
 
-        for result in results.await {
-            self.print_result("Robots ->".to_string(), result);
+    /// Writes a yaml file for each live organization, using the same format read by load_config.
+    ///
+    /// Without an endpoint, every endpoint found in the login config is exported.
+    /// Without an organization, every organization administered by the token is exported.
+    pub async fn export_all(
+        &mut self,
+        endpoint: Option<&String>,
+        organization: Option<&String>,
+        overwrite: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let endpoints: Vec<String> = match endpoint {
+            Some(endpoint) => vec![endpoint.clone()],
+            None => self
+                .quay_login_configs
+                .get_quay_login_configs()
+                .into_iter()
+                .map(|e| e.quay_endpoint)
+                .collect(),
+        };
+
+        if !Path::new(&self.directory).is_dir() {
+            fs::create_dir_all(&self.directory).await?;
         }
 
-        info!(
-            "Robots created in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
-        // Create teams
-        info!("Creating {} teams...", handles_all_teams.len());
+        // Organizations whose live state could not be read, the others are exported anyway
+        let mut failures = 0;
+        for endpoint in endpoints {
+            let user = OrganizationYaml::new(&endpoint, "");
+            let quay_fn_arguments = match self.get_quay_fn_arguments(&user).await {
+                Some(quay_fn_arguments) => quay_fn_arguments,
+                None => continue,
+            };
 
-        let now = Instant::now();
+            let organizations: Vec<String> = match organization {
+                Some(organization) => vec![organization.clone()],
+                None => {
+                    let response = user.get_user(quay_fn_arguments.clone()).await?;
+                    if !response.status_code.is_success() {
+                        error!(
+                            "Unable to read organizations from '{}': status code {}",
+                            endpoint, response.status_code
+                        );
+                        continue;
+                    }
 
-        let results = join_all(handles_all_teams);
+                    let mut organizations = Vec::new();
+                    for org in response.response["organizations"]
+                        .as_array()
+                        .unwrap_or(&Vec::new())
+                    {
+                        if let Some(name) = org["name"].as_str() {
+                            if org["is_org_admin"].as_bool().unwrap_or_default() {
+                                organizations.push(name.to_string());
+                            } else {
+                                warn!("Organization '{}' on '{}' is not administered by the token. Ignoring....", name, endpoint);
+                            }
+                        }
+                    }
+                    organizations
+                }
+            };
 
-        for result in results.await {
-            self.print_result("Teams ->".to_string(), result);
-        }
+            info!(
+                "Exporting {} organizations from {}...",
+                organizations.len(),
+                endpoint
+            );
 
-        info!("Teams created in  {} seconds.", now.elapsed().as_secs_f32());
+            let orgs: Vec<OrganizationYaml> = organizations
+                .iter()
+                .map(|name| OrganizationYaml::new(&endpoint, name))
+                .collect();
+            let now = Instant::now();
+            let results: Vec<_> = stream::iter(&orgs)
+                .map(|org| org.get_live_state(quay_fn_arguments.clone()))
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+
+            for (org, result) in orgs.iter().zip(results) {
+                let mut live = match result {
+                    Ok(Some(live)) => live,
+                    Err(e) => {
+                        error!(
+                            "Unable to read organization '{}' from '{}': {}",
+                            org.quay_organization, endpoint, e
+                        );
+                        failures += 1;
+                        continue;
+                    }
+                    Ok(None) => {
+                        error!(
+                            "Organization '{}' not found on '{}'",
+                            org.quay_organization, endpoint
+                        );
+                        continue;
+                    }
+                };
 
-        // Adding team members
-        info!("Adding {} team members...", handles_all_team_members.len());
-        let now = Instant::now();
-        let results = join_all(handles_all_team_members);
+                // The owners team is created by Quay together with the organization.
+                live.teams.retain(|t| t.name != OWNERS_TEAM);
+                live.teams.sort_by(|a, b| a.name.cmp(&b.name));
+                live.robots.sort_by(|a, b| a.name.cmp(&b.name));
+                live.repositories.sort_by(|a, b| a.name.cmp(&b.name));
+
+                // Yaml files keep the endpoint redirected by the context
+                if let Some((endpoint, _)) = self
+                    .endpoint_map
+                    .iter()
+                    .find(|(_, to)| **to == live.quay_endpoint)
+                {
+                    live.change_endpoint(endpoint.clone());
+                }
 
-        for result in results.await {
-            self.print_result("Team members ->".to_string(), result);
-        }
+                let file_path =
+                    Path::new(&self.directory).join(format!("{}.yaml", live.quay_organization));
+                if file_path.exists() && !overwrite {
+                    warn!(
+                        "{} already exists. Ignoring organization '{}'....",
+                        file_path.display(),
+                        live.quay_organization
+                    );
+                    continue;
+                }
 
-        info!(
-            "Teams members added in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
-        // Create repositories
-        info!(
-            "Creating {} repositories...",
-            handles_all_repositories.len()
-        );
+                let f = File::create(&file_path)?;
+                serde_yaml::to_writer(f, &live)?;
+                info!(
+                    "Organization '{}' exported to {}",
+                    live.quay_organization,
+                    file_path.display()
+                );
 
-        let now = Instant::now();
-        let results = join_all(handles_all_repositories);
+                if live.repositories.iter().any(|r| {
+                    r.mirror_params
+                        .as_ref()
+                        .is_some_and(|m| m.ext_registry_username.is_some())
+                }) {
+                    warn!("Organization '{}' has mirror repositories with external registry credentials. Run qcli login to set the passwords.", live.quay_organization);
+                }
+            }
 
-        for result in results.await {
-            self.print_result("Repository ->".to_string(), result);
+            info!(
+                "Organizations from {} exported in {} seconds.",
+                endpoint,
+                now.elapsed().as_secs_f32()
+            );
         }
 
-        info!(
-            "Repositories created in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
-        //  Get user currently repositories permission (IF ANY)
-        info!(
-            "Delete extra user and robot permission from {} repository...",
-            handles_all_extra_user_permissions.len()
-        );
-        let now = Instant::now();
-        let results = join_all(handles_all_extra_user_permissions);
+        if failures > 0 {
+            return Err(format!("{} organizations could not be exported", failures).into());
+        }
+        Ok(())
+    }
 
-        for result in results.await {
-            self.print_result("Repository USER permissions ->".to_string(), result);
+    /// Reads the live state of every organization and computes the changes needed to reconcile it.
+    pub async fn plan_all(&self) -> Result<Vec<OrganizationPlan>, Box<dyn Error>> {
+        let (mut plans, errors) = self.diff_all().await;
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+        for plan in plans.iter_mut() {
+            plan.retain_prunable();
         }
 
-        info!(
-            "Extra users and robots permissions deleted in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
-        // Get currently team repositories permission (IF ANY)
-        info!(
-            "Delete extra team permission from {} repository...",
-            handles_all_extra_team_permissions.len()
-        );
-        let now = Instant::now();
-        let results = join_all(handles_all_extra_team_permissions);
+        Ok(plans)
+    }
 
-        for result in results.await {
-            self.print_result("Repository TEAM permissions ->".to_string(), result);
+    /// Compares the live state of every organization with the yaml files, changing nothing.
+    /// Returns the report of the differences found. Organizations that could not be read
+    /// are reported as unchecked.
+    pub async fn drift_all(&self, report_path: &str) -> Result<DriftReport, Box<dyn Error>> {
+        let (plans, errors) = self.diff_all().await;
+        for e in &errors {
+            error!("{}", e);
+        }
+        for plan in plans.iter().filter(|p| !p.is_empty()) {
+            self.print_text(plan);
         }
 
-        info!(
-            "Extra teams permissions deleted in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
+        let unchecked: Vec<&OrganizationYaml> = self
+            .get_organizations()
+            .iter()
+            .filter(|org| {
+                !plans.iter().any(|p| {
+                    p.organization.quay_organization == org.quay_organization
+                        && p.organization.quay_endpoint == org.quay_endpoint
+                })
+            })
+            .collect();
 
-        // Create repositories permission
-        info!(
-            "Creating {} repositories permissions...",
-            handles_all_repositories_permissions.len()
-        );
+        let report = DriftReport::new(&plans, &unchecked);
+        let f = File::create(report_path)?;
+        serde_json::to_writer_pretty(f, &report)?;
 
-        let now = Instant::now();
-        let results = join_all(handles_all_repositories_permissions);
+        info!("Drift report written to {}", report_path);
+
+        Ok(report)
+    }
 
-        for result in results.await {
-            self.print_result("Repository permissions ->".to_string(), result);
+    /// Reads the live state of every organization and computes all the differences with the yaml files.
+    /// Returns also the errors of the organizations whose live state could not be read.
+    async fn diff_all(&self) -> (Vec<OrganizationPlan>, Vec<String>) {
+        let now = Instant::now();
+        let results: Vec<Result<OrganizationPlan, String>> = stream::iter(self.get_organizations())
+            .filter_map(|org| async move {
+                info!(
+                    "Reading live state for organization: {}",
+                    org.quay_organization
+                );
+                self.get_quay_fn_arguments(org)
+                    .await
+                    .map(|quay_fn_arguments| async move {
+                        let live = org.get_live_state(quay_fn_arguments).await.map_err(|e| {
+                            format!(
+                                "Unable to read organization '{}' from '{}': {}",
+                                org.quay_organization, org.quay_endpoint, e
+                            )
+                        })?;
+                        Ok(OrganizationPlan::new(org, live.as_ref()))
+                    })
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut plans = Vec::new();
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(plan) => plans.push(plan),
+                Err(e) => errors.push(e),
+            }
         }
 
         info!(
-            "Repositories permissions created in  {} seconds.",
+            "Live state of {} organizations read in {} seconds.",
+            plans.len(),
             now.elapsed().as_secs_f32()
         );
 
-        // Configure repository mirror
-        info!(
-            "Configuring {} repositories mirror...",
-            handles_all_mirror_configurations.len()
-        );
+        (plans, errors)
+    }
 
-        let now = Instant::now();
-        let results = join_all(handles_all_mirror_configurations);
+    /// Prints the plans and the total number of changes.
+    pub fn print_plans(&self, plans: &[OrganizationPlan]) {
+        for plan in plans {
+            self.print_text(plan);
+        }
 
-        for result in results.await {
-            self.print_result("Repository mirror ->".to_string(), result);
+        let count = |change: Change| plans.iter().map(|p| p.count(change)).sum::<usize>();
+        self.print_text(format!(
+            "Plan: {} to add, {} to change, {} to remove.",
+            count(Change::Create),
+            count(Change::Update),
+            count(Change::Delete)
+        ));
+    }
+
+    /// Prints human readable output. With json and junit output, stdout is reserved to the run report.
+    fn print_text(&self, text: impl Display) {
+        match self.output {
+            OutputFormat::Text => println!("{}", text),
+            _ => eprintln!("{}", text),
         }
-        info!(
-            "Repositories mirror configured in  {} seconds.",
-            now.elapsed().as_secs_f32()
-        );
+    }
 
-        Ok(())
-        /*
+    /// Asks the user to confirm the printed plans. The plans are applied without a terminal
+    /// only with --auto-approve, as nobody can read them.
+    pub fn confirm_plans() -> Result<bool, Box<dyn Error>> {
+        if !io::stdin().is_terminal() {
+            return Err(
+                "stdin is not a terminal: use --auto-approve to apply the plan without confirmation"
+                    .into(),
+            );
+        }
+
+        Ok(ask_yes_no("Do you want to perform these actions?", false)?.unwrap_or(false))
+    }
 
-        */
+    /// Executes the plans, each one as an independent graph of tasks.
+    pub async fn apply_all(&self, plans: &[OrganizationPlan]) -> Result<(), Box<dyn Error>> {
+        let now = Instant::now();
+        let runs: Vec<OrganizationRun> = stream::iter(plans)
+            .filter_map(|plan| async move {
+                self.get_quay_fn_arguments(&plan.organization)
+                    .await
+                    .map(|quay_fn_arguments| plan.execute(quay_fn_arguments))
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        self.print_runs(&runs, now)
     }
 }
 
+/// Context name selecting the top level logins, without endpoint remapping.
+const DEFAULT_CONTEXT: &str = "default";
+
 // Configuration struct contaning oauth token for each Quay endpoints
+//
+// Named contexts redirect the quay_endpoint of the yaml files to the endpoints of an
+// environment, with their own tokens. The top level logins are shared by every context.
+// ```
+// quay_endpoint_login:
+// - quay_endpoint: quay.example.com
+//   quay_token: <token>
+// current_context: prod
+// contexts:
+// - name: prod
+//   endpoints:
+//     quay.example.com: quay-prod.example.com
+//   quay_endpoint_login:
+//   - quay_endpoint: quay-prod.example.com
+//     quay_token: <token>
+// ```
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 struct QuayLoginConfigs {
+    #[serde(default)]
     pub quay_endpoint_login: Vec<QuayEndopoint>,
     pub mirror_repository: Option<Vec<MirrorLogin>>,
+    /// Context used when --context is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contexts: Vec<LoginContext>,
+}
+
+/// Named environment of the login file, like dev, staging or prod.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+struct LoginContext {
+    pub name: String,
+    /// Quay endpoints of the yaml files, mapped to the endpoints of the context
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub endpoints: BTreeMap<String, String>,
+    /// Tokens and mirror passwords of the context, replacing the top level ones
+    #[serde(flatten)]
+    pub logins: QuayLoginConfigs,
 }
 
 impl QuayLoginConfigs {
@@ -782,21 +1521,261 @@ impl QuayLoginConfigs {
         self.quay_endpoint_login.clone()
     }
 
-    pub fn get_token_from_quay_endopoint(&self, endpoint: String) -> Option<String> {
-        for configured_endpoint in self.get_quay_login_configs() {
-            if configured_endpoint.quay_endpoint == endpoint {
-                return Some(configured_endpoint.quay_token);
+    /// Returns the login of the endpoint, if it has a token or a token source.
+    pub fn get_quay_endpoint_login(&self, endpoint: &str) -> Option<&QuayEndopoint> {
+        self.quay_endpoint_login.iter().find(|e| {
+            e.quay_endpoint == endpoint && (!e.quay_token.is_empty() || e.quay_token_from.is_some())
+        })
+    }
+
+    /// Returns the login of the mirror user, if it has a password or a password source.
+    pub fn get_mirror_login(&self, mirror: &MirrorLogin) -> Option<&MirrorLogin> {
+        self.mirror_repository
+            .iter()
+            .flatten()
+            .find(|m| m.is_same_user(mirror) && m.has_password())
+    }
+
+    /// Returns the name of the context selected with `context`, or by current_context.
+    /// `None` if the top level logins are used.
+    fn context_name(&self, context: Option<&str>) -> Option<String> {
+        match context.or(self.current_context.as_deref()) {
+            None | Some(DEFAULT_CONTEXT) => None,
+            Some(name) => Some(name.to_string()),
+        }
+    }
+
+    /// Returns the context, or an error listing the available ones.
+    fn get_context(&self, name: &str) -> Result<&LoginContext, Box<dyn Error>> {
+        self.contexts
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| {
+                let available: Vec<&str> = self.contexts.iter().map(|c| c.name.as_str()).collect();
+                format!(
+                    "Context '{}' not found. Available contexts: {}",
+                    name,
+                    [&[DEFAULT_CONTEXT], available.as_slice()]
+                        .concat()
+                        .join(", ")
+                )
+                .into()
+            })
+    }
+
+    /// Returns the logins of the selected context merged over the top level ones, and the
+    /// endpoint remapping of the context.
+    fn select_context(
+        &self,
+        context: Option<&str>,
+    ) -> Result<(QuayLoginConfigs, BTreeMap<String, String>), Box<dyn Error>> {
+        let mut logins = QuayLoginConfigs {
+            quay_endpoint_login: self.quay_endpoint_login.clone(),
+            mirror_repository: self.mirror_repository.clone(),
+            ..Default::default()
+        };
+
+        match self.context_name(context) {
+            Some(name) => {
+                let context = self.get_context(&name)?;
+                logins.merge(context.logins.clone());
+                Ok((logins, context.endpoints.clone()))
+            }
+            None => Ok((logins, BTreeMap::new())),
+        }
+    }
+
+    /// Returns the logins of the context, added if missing, or the top level logins.
+    fn logins_mut(&mut self, context: Option<&str>) -> &mut QuayLoginConfigs {
+        let Some(name) = context else {
+            return self;
+        };
+        let index = match self.contexts.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => {
+                self.contexts.push(LoginContext {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.contexts.len() - 1
+            }
+        };
+        &mut self.contexts[index].logins
+    }
+
+    /// Checks the contexts: unique names, not nested, and current_context defined.
+    fn validate_contexts(&self) -> Result<(), Box<dyn Error>> {
+        let mut names = HashSet::new();
+        for context in &self.contexts {
+            if context.name == DEFAULT_CONTEXT || !names.insert(&context.name) {
+                return Err(format!("Context '{}' defined twice", context.name).into());
+            }
+            if !context.logins.contexts.is_empty() || context.logins.current_context.is_some() {
+                return Err(
+                    format!("Context '{}': contexts can not be nested", context.name).into(),
+                );
+            }
+        }
+        if let Some(name) = self.context_name(None) {
+            self.get_context(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the endpoints and mirror logins of `other`, replacing the ones already present.
+    /// Contexts with the same name are merged.
+    fn merge(&mut self, other: QuayLoginConfigs) {
+        for endpoint in other.quay_endpoint_login {
+            self.quay_endpoint_login
+                .retain(|e| e.quay_endpoint != endpoint.quay_endpoint);
+            self.quay_endpoint_login.push(endpoint);
+        }
+
+        if let Some(mirrors) = other.mirror_repository {
+            let current = self.mirror_repository.get_or_insert_with(Vec::new);
+            for mirror in mirrors {
+                current.retain(|m| !m.is_same_user(&mirror));
+                current.push(mirror);
             }
         }
 
-        None
+        if other.current_context.is_some() {
+            self.current_context = other.current_context;
+        }
+        for context in other.contexts {
+            match self.contexts.iter_mut().find(|c| c.name == context.name) {
+                Some(current) => {
+                    current.endpoints.extend(context.endpoints);
+                    current.logins.merge(context.logins);
+                }
+                None => self.contexts.push(context),
+            }
+        }
+    }
+
+    /// Masks the tokens and passwords, of the contexts too.
+    fn mask_secrets(&mut self) {
+        for endpoint in &mut self.quay_endpoint_login {
+            endpoint.quay_token = mask_secret(&endpoint.quay_token);
+        }
+        for mirror in self.mirror_repository.iter_mut().flatten() {
+            mirror.ext_registry_password = mask_secret(&mirror.ext_registry_password);
+        }
+        for context in &mut self.contexts {
+            context.logins.mask_secrets();
+        }
+    }
+
+    /// Returns true if tokens or passwords are saved in the login file, not only their sources.
+    fn has_secrets(&self) -> bool {
+        self.quay_endpoint_login
+            .iter()
+            .any(|e| !e.quay_token.is_empty())
+            || self
+                .mirror_repository
+                .iter()
+                .flatten()
+                .any(|m| !m.ext_registry_password.is_empty())
+            || self.contexts.iter().any(|c| c.logins.has_secrets())
+    }
+}
+
+/// Asks a yes/no question on stdin, until a valid answer is given.
+/// An empty answer is the default, `None` means stdin was closed.
+///
+/// The question is written to stderr, as stdout may be reserved to the run report.
+/// Stdin is not buffered past the answer, so the next lines can still be read.
+fn ask_yes_no(question: &str, default: bool) -> Result<Option<bool>, Box<dyn Error>> {
+    loop {
+        eprint!("{} ({}) ", question, if default { "Y/n" } else { "y/N" });
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "" => return Ok(Some(default)),
+            "y" | "yes" => return Ok(Some(true)),
+            "n" | "no" => return Ok(Some(false)),
+            _ => continue,
+        }
+    }
+}
+
+/// Reads a secret from the terminal without echo, or a line from stdin if it is not a terminal.
+/// Empty secrets are an error.
+fn read_secret(prompt: &str) -> Result<String, Box<dyn Error>> {
+    let secret = if io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt)?
+    } else {
+        eprint!("{}", prompt);
+        io::stderr().flush()?;
+        let mut secret = String::new();
+        io::stdin().read_line(&mut secret)?;
+        secret
+    };
+
+    match secret.trim() {
+        "" => Err(format!(
+            "Nothing entered for: {}",
+            prompt.trim_end_matches([' ', ':'])
+        )
+        .into()),
+        secret => Ok(secret.to_string()),
+    }
+}
+
+/// Masks a secret, leaving its last 4 characters visible if it is long enough.
+fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    match chars.len() {
+        0 => String::new(),
+        n if n > 12 => format!("********{}", chars[n - 4..].iter().collect::<String>()),
+        _ => "********".to_string(),
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 struct QuayEndopoint {
     pub quay_endpoint: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub quay_token: String,
+    /// Source of the token, used instead of quay_token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quay_token_from: Option<CredentialSource>,
+    /// Max requests per second sent to the endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<u32>,
+    /// PEM bundle of the CAs trusted for the endpoint, besides the system ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<String>,
+    /// PEM client certificate, for endpoints requiring mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// PEM private key of the client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+}
+
+/// Options of the login subcommand.
+#[derive(Debug, Clone, Default)]
+pub struct LoginArguments {
+    /// Keep the existing login file, asking only the missing tokens and mirror passwords
+    pub merge: bool,
+    /// Read the secrets from a login yaml or json document on stdin instead of asking them
+    pub non_interactive: bool,
+}
+
+/// TLS settings of the HTTP client of a Quay endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Verify the Quay certificate, `None` to use the yaml files setting
+    pub verify: Option<bool>,
+    /// PEM bundle of trusted CAs, besides the system ones
+    pub ca_file: Option<String>,
+    /// PEM client certificate and private key, for mutual TLS
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
 }
 
 // Configuration struct for saving mirroring password for each repositories (if mirror username exists)
@@ -824,6 +1803,11 @@ struct QuayEndopoint {
 //     ext_registry_username: <ext_registry_username>
 //     ext_registry_password:
 //
+// or, to read the password when needed instead of saving it:
+//
+//     ext_registry_password_from:
+//       command: ["pass", "show", "registry/valeidm"]
+//
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct QuayMirrorLogin {
@@ -835,5 +1819,23 @@ pub struct MirrorLogin {
     pub organization: String,
     pub repository: String,
     pub ext_registry_username: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub ext_registry_password: String,
+    /// Source of the password, used instead of ext_registry_password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ext_registry_password_from: Option<CredentialSource>,
+}
+
+impl MirrorLogin {
+    /// Returns true if both are the login of the same user for the same repository.
+    fn is_same_user(&self, other: &MirrorLogin) -> bool {
+        self.organization == other.organization
+            && self.repository == other.repository
+            && self.ext_registry_username == other.ext_registry_username
+    }
+
+    /// Returns true if the password or its source is set.
+    pub fn has_password(&self) -> bool {
+        !self.ext_registry_password.is_empty() || self.ext_registry_password_from.is_some()
+    }
 }
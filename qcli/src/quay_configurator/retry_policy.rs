@@ -0,0 +1,70 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime};
+
+/// Retry policy of the requests sent to Quay.
+///
+/// Connection errors, timeouts, 429 and 5xx responses are retried with an exponential backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Max number of attempts of a request, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled at each attempt
+    pub base_delay: Duration,
+    /// Max delay between two attempts, the delay asked by Quay included
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// True if a response with this status code must be retried.
+    pub fn is_retryable_status(status_code: StatusCode) -> bool {
+        status_code == StatusCode::TOO_MANY_REQUESTS || status_code.is_server_error()
+    }
+
+    /// True if the transport error must be retried.
+    pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+        error.is_connect() || error.is_timeout() || error.is_request()
+    }
+
+    /// Delay before the next attempt, after `attempt` failed attempts.
+    ///
+    /// The delay sent by Quay in the Retry-After header wins over the backoff, up to `max_delay`,
+    /// otherwise a random delay between half and the whole exponential backoff is used.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        let half = backoff / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+
+    /// Parses the Retry-After header, both as seconds and as HTTP date.
+    pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        match value.parse::<u64>() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => httpdate::parse_http_date(value)
+                .ok()?
+                .duration_since(SystemTime::now())
+                .ok(),
+        }
+    }
+}
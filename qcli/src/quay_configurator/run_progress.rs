@@ -0,0 +1,140 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Live progress bars of a run: a bar for each organization and a total bar.
+///
+/// Bars show completed and total tasks, the ETA and the rate of the requests let through
+/// by the endpoint rate limiter.
+#[derive(Debug, Clone)]
+pub struct RunProgress {
+    multi: MultiProgress,
+    state: Arc<Mutex<ProgressState>>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    total: Option<ProgressBar>,
+    /// Bars of the organizations, with their endpoint
+    organizations: HashMap<(String, String), ProgressBar>,
+    /// Requests sent to each endpoint
+    requests: HashMap<String, Arc<AtomicU64>>,
+}
+
+impl RunProgress {
+    pub fn new(multi: MultiProgress) -> RunProgress {
+        RunProgress {
+            multi,
+            state: Arc::new(Mutex::new(ProgressState::default())),
+        }
+    }
+
+    fn style(template: &str) -> ProgressStyle {
+        ProgressStyle::with_template(template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> ")
+    }
+
+    /// Adds `tasks` tasks to the organization's bar, creating it if needed.
+    pub fn add_tasks(&self, quay_endpoint: &str, quay_organization: &str, tasks: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.total.is_none() {
+            let total = self.multi.add(ProgressBar::new(0));
+            total.set_style(Self::style(
+                "{prefix:>20.bold} [{bar:40.green}] {pos}/{len} tasks, {msg} (ETA {eta})",
+            ));
+            total.set_prefix("total");
+            state.total = Some(total);
+            self.start_ticker();
+        }
+
+        let key = (quay_endpoint.to_string(), quay_organization.to_string());
+        let bar = state
+            .organizations
+            .entry(key)
+            .or_insert_with(|| {
+                let bar = self.multi.add(ProgressBar::new(0));
+                bar.set_style(Self::style(
+                    "{prefix:>20} [{bar:40.cyan}] {pos}/{len} tasks, {msg} (ETA {eta})",
+                ));
+                bar.set_prefix(quay_organization.to_string());
+                bar
+            })
+            .clone();
+        bar.inc_length(tasks as u64);
+
+        if let Some(total) = &state.total {
+            total.inc_length(tasks as u64);
+        }
+    }
+
+    /// Marks a task of the organization as done.
+    pub fn task_done(&self, quay_endpoint: &str, quay_organization: &str) {
+        let state = self.state.lock().unwrap();
+        let key = (quay_endpoint.to_string(), quay_organization.to_string());
+        if let Some(bar) = state.organizations.get(&key) {
+            bar.inc(1);
+        }
+        if let Some(total) = &state.total {
+            total.inc(1);
+        }
+    }
+
+    /// Counter of the requests sent to the endpoint, shown as request rate.
+    pub fn request_counter(&self, quay_endpoint: &str) -> Arc<AtomicU64> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .requests
+            .entry(quay_endpoint.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Stops and clears the bars, before the run report is printed.
+    pub fn finish(&self) {
+        let state = self.state.lock().unwrap();
+        for bar in state.organizations.values() {
+            bar.finish_and_clear();
+        }
+        if let Some(total) = &state.total {
+            total.finish_and_clear();
+        }
+    }
+
+    /// Updates the request rate shown by the bars every second.
+    fn start_ticker(&self) {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut last: HashMap<String, u64> = HashMap::new();
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                let progress = state.lock().unwrap();
+
+                let mut rates = HashMap::new();
+                for (endpoint, counter) in &progress.requests {
+                    let count = counter.load(Ordering::Relaxed);
+                    let previous = last.insert(endpoint.clone(), count).unwrap_or_default();
+                    rates.insert(endpoint.clone(), count - previous);
+                }
+
+                for ((endpoint, _), bar) in &progress.organizations {
+                    bar.set_message(format!(
+                        "{} req/s",
+                        rates.get(endpoint).copied().unwrap_or_default()
+                    ));
+                }
+
+                match &progress.total {
+                    Some(total) if !total.is_finished() => {
+                        total.set_message(format!("{} req/s", rates.values().sum::<u64>()))
+                    }
+                    _ => break,
+                }
+            }
+        });
+    }
+}
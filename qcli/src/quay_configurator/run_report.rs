@@ -0,0 +1,305 @@
+use super::organization_plan::Phase;
+use super::organization_scheduler::{OrganizationRun, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// Format of the run report printed to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Junit,
+}
+
+/// End of run report: the executed tasks grouped by organization and phase.
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    pub organizations: Vec<OrganizationReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrganizationReport {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+    pub phases: Vec<PhaseReport>,
+    /// Errors not related to a single task
+    pub errors: Vec<String>,
+    pub operations: Vec<OperationRecord>,
+}
+
+/// Result of a single API operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationRecord {
+    pub quay_endpoint: String,
+    pub quay_organization: String,
+    pub phase: String,
+    pub change: &'static str,
+    pub object_type: &'static str,
+    pub object: String,
+    pub method: Option<String>,
+    pub status_code: Option<u16>,
+    pub status: &'static str,
+    /// Description of the request, e.g. "Creating repository 'alpine' for organization 'exampleorg'"
+    pub description: Option<String>,
+    /// Status returned by Quay with its error message, or why the operation failed or was skipped
+    pub message: String,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhaseReport {
+    pub phase: Phase,
+    pub succeeded: usize,
+    /// Creations of objects that already exist
+    pub unchanged: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Failed objects with the error returned by Quay
+    pub failures: Vec<String>,
+}
+
+impl RunReport {
+    pub fn new(runs: &[OrganizationRun]) -> RunReport {
+        let organizations = runs
+            .iter()
+            .map(|run| {
+                let phases = Phase::ALL
+                    .iter()
+                    .filter_map(|&phase| {
+                        let tasks: Vec<_> = run
+                            .tasks
+                            .iter()
+                            .filter(|t| t.action.phase() == phase)
+                            .collect();
+                        if tasks.is_empty() {
+                            return None;
+                        }
+                        let count = |status| tasks.iter().filter(|t| t.status == status).count();
+                        Some(PhaseReport {
+                            phase,
+                            succeeded: count(TaskStatus::Succeeded),
+                            unchanged: count(TaskStatus::Unchanged),
+                            failed: count(TaskStatus::Failed),
+                            skipped: count(TaskStatus::Skipped),
+                            failures: tasks
+                                .iter()
+                                .filter(|t| t.status == TaskStatus::Failed)
+                                .map(|t| format!("{} -> {}", t.action.object, t.message))
+                                .collect(),
+                        })
+                    })
+                    .collect();
+
+                let operations = run
+                    .tasks
+                    .iter()
+                    .map(|t| OperationRecord {
+                        quay_endpoint: run.quay_endpoint.clone(),
+                        quay_organization: run.quay_organization.clone(),
+                        phase: t.action.phase().to_string(),
+                        change: t.action.change.name(),
+                        object_type: t.action.object.kind(),
+                        object: t.action.object.to_string(),
+                        method: t.method.clone(),
+                        status_code: t.status_code,
+                        status: t.status.name(),
+                        description: t.description.clone(),
+                        message: t.message.clone(),
+                        duration_ms: t.duration.as_millis(),
+                    })
+                    .collect();
+
+                OrganizationReport {
+                    quay_endpoint: run.quay_endpoint.clone(),
+                    quay_organization: run.quay_organization.clone(),
+                    phases,
+                    errors: run.errors.clone(),
+                    operations,
+                }
+            })
+            .collect();
+
+        RunReport { organizations }
+    }
+
+    /// Number of organizations with failed tasks or errors.
+    pub fn failed_organizations(&self) -> usize {
+        self.organizations
+            .iter()
+            .filter(|o| !o.errors.is_empty() || o.phases.iter().any(|p| p.failed > 0))
+            .count()
+    }
+
+    fn total(&self, count: fn(&PhaseReport) -> usize) -> usize {
+        self.organizations
+            .iter()
+            .flat_map(|o| &o.phases)
+            .map(count)
+            .sum()
+    }
+
+    /// Renders the report in the requested format.
+    pub fn render(&self, format: OutputFormat) -> Result<String, Box<dyn Error>> {
+        match format {
+            OutputFormat::Text => Ok(self.to_string()),
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(&self.to_json())?),
+            OutputFormat::Junit => Ok(self.to_junit()),
+        }
+    }
+
+    fn to_json(&self) -> JsonReport<'_> {
+        JsonReport {
+            succeeded: self.total(|p| p.succeeded),
+            unchanged: self.total(|p| p.unchanged),
+            failed: self.total(|p| p.failed),
+            skipped: self.total(|p| p.skipped),
+            operations: self
+                .organizations
+                .iter()
+                .flat_map(|o| &o.operations)
+                .collect(),
+            errors: self
+                .organizations
+                .iter()
+                .flat_map(|o| {
+                    o.errors.iter().map(|message| JsonError {
+                        quay_endpoint: &o.quay_endpoint,
+                        quay_organization: &o.quay_organization,
+                        message,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// JUnit XML report: a test suite for each organization, a test case for each operation.
+    fn to_junit(&self) -> String {
+        let mut suites = String::new();
+        let (mut tests, mut failures, mut skipped) = (0, 0, 0);
+
+        for org in &self.organizations {
+            let org_failures = org
+                .operations
+                .iter()
+                .filter(|o| o.status == "failed")
+                .count()
+                + org.errors.len();
+            let org_skipped = org
+                .operations
+                .iter()
+                .filter(|o| o.status == "skipped")
+                .count();
+            let org_tests = org.operations.len() + org.errors.len();
+            let time: u128 = org.operations.iter().map(|o| o.duration_ms).sum();
+            tests += org_tests;
+            failures += org_failures;
+            skipped += org_skipped;
+
+            suites.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+                xml_escape(&format!("{}/{}", org.quay_endpoint, org.quay_organization)),
+                org_tests,
+                org_failures,
+                org_skipped,
+                time as f64 / 1000.0
+            ));
+            for operation in &org.operations {
+                suites.push_str(&format!(
+                    "    <testcase classname=\"{}\" name=\"{} {}\" time=\"{:.3}\"",
+                    xml_escape(&operation.phase),
+                    operation.change,
+                    xml_escape(&operation.object),
+                    operation.duration_ms as f64 / 1000.0
+                ));
+                match operation.status {
+                    "failed" => suites.push_str(&format!(
+                        ">\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                        xml_escape(&operation.message)
+                    )),
+                    "skipped" => suites.push_str(&format!(
+                        ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                        xml_escape(&operation.message)
+                    )),
+                    _ => suites.push_str("/>\n"),
+                }
+            }
+            for e in &org.errors {
+                suites.push_str(&format!(
+                    "    <testcase classname=\"organization\" name=\"organization\">\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                    xml_escape(e)
+                ));
+            }
+            suites.push_str("  </testsuite>\n");
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"qcli\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n{}</testsuites>",
+            tests, failures, skipped, suites
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    succeeded: usize,
+    unchanged: usize,
+    failed: usize,
+    skipped: usize,
+    operations: Vec<&'a OperationRecord>,
+    errors: Vec<JsonError<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    quay_endpoint: &'a str,
+    quay_organization: &'a str,
+    message: &'a str,
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for org in &self.organizations {
+            writeln!(
+                f,
+                "Organization '{}' on endpoint '{}':",
+                org.quay_organization, org.quay_endpoint
+            )?;
+            if org.phases.is_empty() && org.errors.is_empty() {
+                writeln!(f, "  Nothing to do.")?;
+            }
+            for phase in &org.phases {
+                writeln!(
+                    f,
+                    "  {}: {} succeeded, {} unchanged, {} failed, {} skipped",
+                    phase.phase, phase.succeeded, phase.unchanged, phase.failed, phase.skipped
+                )?;
+                for failure in &phase.failures {
+                    writeln!(f, "    ! {}", failure)?;
+                }
+            }
+            for e in &org.errors {
+                writeln!(f, "  ! {}", e)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Run: {} succeeded, {} unchanged, {} failed, {} skipped in {} organizations.",
+            self.total(|p| p.succeeded),
+            self.total(|p| p.unchanged),
+            self.total(|p| p.failed),
+            self.total(|p| p.skipped),
+            self.organizations.len()
+        )
+    }
+}
@@ -0,0 +1,236 @@
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// Renders the `{{ }}` expressions of an organization yaml file, before it is deserialized.
+///
+/// Lines are kept, so yaml errors of the rendered file point at the lines of the original one.
+/// Expressions are evaluated only in quoted strings, as in Ansible, and their values are escaped
+/// for the string, so they can not change the structure of the file. Expressions outside quoted
+/// strings are an error, except in comments, which are not rendered.
+/// ```yaml
+/// vars:
+///   team: payments
+/// quay_organization: "{{ team }}-prod"
+/// quay_organization_role_name: "{{ quay_organization }}"
+/// quay_organization_role_email: "{{ env.TEAM_EMAIL | default('quay@example.com') }}"
+/// teams:
+///   - name: "{{ team | lower }}"
+/// ```
+/// A name is looked up in the `--var` values, then in the `vars` block, then in the fields of
+/// the file. Dotted names walk the file from its top level, `env.NAME` reads an env variable.
+/// Filters: `lower`, and `default('value')` used when the name is not defined or empty.
+pub fn render(content: &str, vars: &BTreeMap<String, String>) -> Result<String, Box<dyn Error>> {
+    if !content.contains(OPEN) {
+        return Ok(content.to_string());
+    }
+
+    let template = Template {
+        document: serde_yaml::from_str(content)?,
+        vars,
+    };
+
+    let mut rendered = String::with_capacity(content.len());
+    // Quoted strings can span several lines
+    let mut quote = None;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line = template
+            .render_line(line, &mut quote)
+            .map_err(|e| format!("line {}: {}", index + 1, e))?;
+        rendered.push_str(&line);
+    }
+    Ok(rendered)
+}
+
+struct Template<'a> {
+    /// The file before rendering
+    document: Value,
+    /// Values given on the command line
+    vars: &'a BTreeMap<String, String>,
+}
+
+impl Template<'_> {
+    /// Renders the expressions of a line of the file, escaping their value for the single or
+    /// double quoted string they are in.
+    /// `quote` is the quote of the string open at the start of the line, if any, and is updated
+    /// with the one still open at its end.
+    fn render_line(&self, line: &str, quote: &mut Option<char>) -> Result<String, String> {
+        let mut rendered = String::with_capacity(line.len());
+        // True where a quoted string can start: at the start of a key or of a value
+        let mut scalar_start = quote.is_none();
+        let mut previous = ' ';
+        let mut rest = line;
+
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with(OPEN) {
+                let expression = &rest[OPEN.len()..];
+                let end = expression
+                    .find(CLOSE)
+                    .ok_or_else(|| format!("'{}' not closed", OPEN))?;
+                let value =
+                    self.render_text(&rest[..OPEN.len() + end + CLOSE.len()], &mut Vec::new())?;
+                match quote {
+                    Some('"') => {
+                        rendered.push_str(&value.replace('\\', "\\\\").replace('"', "\\\""))
+                    }
+                    Some(_) => rendered.push_str(&value.replace('\'', "''")),
+                    None => {
+                        return Err(format!(
+                            "'{}' is not in a quoted string",
+                            expression[..end].trim()
+                        ))
+                    }
+                }
+                rest = &expression[end + CLOSE.len()..];
+                scalar_start = false;
+                previous = '}';
+                continue;
+            }
+
+            let mut length = c.len_utf8();
+            match (*quote, c) {
+                (None, '#') if previous.is_whitespace() => {
+                    rendered.push_str(rest);
+                    return Ok(rendered);
+                }
+                (None, '"' | '\'') if scalar_start => *quote = Some(c),
+                (Some('"'), '\\') => {
+                    length += rest[1..].chars().next().map_or(0, char::len_utf8);
+                }
+                (Some('\''), '\'') if rest[1..].starts_with('\'') => length += 1,
+                (Some(q), _) if c == q => *quote = None,
+                _ => {}
+            }
+            scalar_start = quote.is_none()
+                && (matches!(c, ':' | '-' | '[' | '{' | ',')
+                    || (c.is_whitespace() && scalar_start));
+            previous = c;
+            rendered.push_str(&rest[..length]);
+            rest = &rest[length..];
+        }
+
+        Ok(rendered)
+    }
+
+    /// Replaces the expressions of the text with their value.
+    /// `resolving` are the names being rendered, to detect circular references.
+    fn render_text(&self, text: &str, resolving: &mut Vec<String>) -> Result<String, String> {
+        let mut rendered = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find(OPEN) {
+            rendered.push_str(&rest[..start]);
+            let expression = &rest[start + OPEN.len()..];
+            let end = expression
+                .find(CLOSE)
+                .ok_or_else(|| format!("'{}' not closed", OPEN))?;
+
+            let value = self.evaluate(&expression[..end], resolving)?;
+            if value.contains('\n') {
+                return Err(format!(
+                    "'{}' has more than one line",
+                    expression[..end].trim()
+                ));
+            }
+            rendered.push_str(&value);
+            rest = &expression[end + CLOSE.len()..];
+        }
+
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+
+    /// Evaluates `name | filter | filter(...)`.
+    fn evaluate(&self, expression: &str, resolving: &mut Vec<String>) -> Result<String, String> {
+        let mut parts = expression.split('|');
+        let name = parts.next().unwrap_or_default().trim();
+        let mut value = self.lookup(name, resolving)?;
+
+        for filter in parts {
+            let filter = filter.trim();
+            value = match filter.split_once('(') {
+                None if filter == "lower" => value.map(|v| v.to_lowercase()),
+                Some((function, argument)) if function.trim() == "default" => {
+                    let default = argument
+                        .trim_end()
+                        .strip_suffix(')')
+                        .and_then(literal)
+                        .ok_or_else(|| format!("Invalid filter '{}'", filter))?;
+                    value.filter(|v| !v.is_empty()).or(Some(default))
+                }
+                _ => return Err(format!("Unknown filter '{}'", filter)),
+            };
+        }
+
+        value.ok_or_else(|| format!("'{}' is not defined", name))
+    }
+
+    /// Returns the value of a name or of a string literal, `None` if the name is not defined.
+    fn lookup(&self, name: &str, resolving: &mut Vec<String>) -> Result<Option<String>, String> {
+        if let Some(literal) = literal(name) {
+            return Ok(Some(literal));
+        }
+        let valid = |segment: &str| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        if !name.split('.').all(valid) {
+            return Err(format!("Invalid expression '{}'", name));
+        }
+
+        if let Some(env) = name.strip_prefix("env.") {
+            return Ok(std::env::var(env).ok());
+        }
+        if let Some(value) = self.vars.get(name) {
+            return Ok(Some(value.clone()));
+        }
+
+        let mut value = self
+            .document
+            .get("vars")
+            .and_then(|vars| vars.get(name))
+            .filter(|_| !name.contains('.'));
+        if value.is_none() {
+            value = name.split('.').try_fold(&self.document, |value, segment| {
+                match segment.parse::<usize>() {
+                    Ok(index) if value.is_sequence() => value.get(index),
+                    _ => value.get(segment),
+                }
+            });
+        }
+
+        match value {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Bool(b)) => Ok(Some(b.to_string())),
+            Some(Value::Number(n)) => Ok(Some(n.to_string())),
+            Some(Value::String(s)) => {
+                if resolving.iter().any(|r| r == name) {
+                    return Err(format!(
+                        "Circular reference: {} -> {}",
+                        resolving.join(" -> "),
+                        name
+                    ));
+                }
+                resolving.push(name.to_string());
+                let rendered = self.render_text(s, resolving)?;
+                resolving.pop();
+                Ok(Some(rendered))
+            }
+            Some(_) => Err(format!("'{}' is not a single value", name)),
+        }
+    }
+}
+
+/// Returns the content of a single or double quoted string.
+fn literal(text: &str) -> Option<String> {
+    let text = text.trim();
+    ['"', '\'']
+        .iter()
+        .find(|quote| text.len() >= 2 && text.starts_with(**quote) && text.ends_with(**quote))
+        .map(|_| text[1..text.len() - 1].to_string())
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+
+#[tokio::test]
+async fn check_accepts_example_files() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let output = workspace.qcli(&["check"], "").await;
+
+    assert!(output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(logs.matches("Config syntax of").count(), 6);
+    // The configuration is checked without contacting Quay
+    assert!(quay.state().requests.is_empty());
+}
+
+#[tokio::test]
+async fn check_fails_without_mirror_password() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+
+    let output = workspace.qcli(&["check"], "").await;
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Missing mirror user password"));
+}
+
+#[tokio::test]
+async fn check_fails_with_invalid_endpoint() {
+    let workspace = Workspace::new(
+        "https://quay.example.com:badport",
+        &["example-organization3.yaml"],
+    );
+
+    let output = workspace.qcli(&["check"], "").await;
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Invalid Quay endpoint"));
+}
+
+#[tokio::test]
+async fn check_reports_every_problem_with_its_line() {
+    let workspace = Workspace::new("quay.example.com", &["none"]);
+    workspace.write_yaml(
+        "broken.yaml",
+        "---
+quay_endpoint: \"quay.example.com\"
+replicate_to:
+  - \"quay.example.com\"
+  - replica.example.com
+  - replica.example.com
+quay_validate_certs: \"yes\"
+quay_organization: brokenorg
+quay_organization_role_name: \"\"
+quay_organization_role_email: \"\"
+repositories:
+  - name: app
+    visibility: internal
+    mirror: true
+    mirror_params:
+      src_registry: quay.io
+      src_image: libpod/alpine
+      src_image_tags: [\"latest\"]
+      ext_registry_verify_tls: true
+      robot_username: mirrorbot
+      sync_interval: 0
+      is_enabled: true
+    permissions:
+      robots:
+        - name: ghost
+          role: read
+      users:
+        - name: someone
+          role: owner
+      teams:
+        - name: devs
+          role: write
+  - name: app
+    mirror: false
+  - name: Bad_Name
+    mirror: false
+robots:
+  - name: reader
+    desc: \"\"
+teams:
+  - name: ops
+    description: \"\"
+    members:
+      users: []
+      robots:
+        - brokenorg+reader
+        - phantom
+    role: superuser
+",
+    );
+
+    let output = workspace.qcli(&["check"], "").await;
+
+    assert!(!output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    for problem in [
+        "line 4: replicate_to contains the quay_endpoint 'quay.example.com'",
+        "line 6: replicate_to contains 'replica.example.com' more than once",
+        "line 13: visibility 'internal' of repository 'app' is not public or private",
+        "line 20: robot 'mirrorbot' of the mirror of repository 'app' is not declared in robots",
+        "line 21: sync_interval of the mirror of repository 'app' must be positive",
+        "line 25: robot 'ghost' of the permissions of repository 'app' is not declared in robots",
+        "line 29: role 'owner' of user 'someone' on repository 'app' is not one of read, write, admin",
+        "line 31: team 'devs' of the permissions of repository 'app' is not declared in teams",
+        "line 33: repository 'app' is declared more than once",
+        "line 35: repository name 'Bad_Name' is not valid",
+        "line 47: robot 'phantom' member of team 'ops' is not declared in robots",
+        "line 48: role 'superuser' of team 'ops' is not one of member, creator, admin",
+        "12 problem(s) found",
+    ] {
+        assert!(
+            logs.contains(&format!("yaml-files/broken.yaml: {}", problem))
+                || problem.ends_with("found") && logs.contains(problem),
+            "'{}' not reported:\n{}",
+            problem,
+            logs
+        );
+    }
+}
+
+#[tokio::test]
+async fn robots_can_be_referenced_with_the_organization_prefix() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    workspace.edit_yaml(
+        "example-organization.yaml",
+        "robot_username: writer",
+        "robot_username: exampleorg+writer",
+    );
+    workspace.edit_yaml(
+        "example-organization.yaml",
+        "            - name: reader\n",
+        "            - name: exampleorg+reader\n",
+    );
+    workspace.edit_yaml(
+        "example-organization.yaml",
+        "      robots:\n        - writer",
+        "      robots:\n        - exampleorg+writer",
+    );
+
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // The prefix is not added twice by the requests
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    let org = quay.organization("exampleorg").unwrap();
+    let alpine = &org.repositories["alpine"];
+    assert_eq!(alpine.user_permissions["exampleorg+reader"], "read");
+    assert_eq!(
+        alpine.mirror.as_ref().unwrap()["robot_username"],
+        "exampleorg+writer"
+    );
+    assert!(org.teams["maintainers"]
+        .members
+        .contains("exampleorg+writer"));
+
+    let output = workspace.qcli(&["plan"], "").await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Plan: 0 to add, 0 to change, 0 to remove."),
+        "{}",
+        stdout
+    );
+}
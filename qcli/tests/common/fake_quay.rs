@@ -0,0 +1,743 @@
+//! In-memory fake of the Quay API, served on a random local port over TLS, mutual TLS or plain HTTP.
+//!
+//! Only the endpoints used by qcli are implemented, with the status codes returned by Quay:
+//! 201 on creation, 204 on deletion, 400 when the object already exists, 404 when it does not
+//! exist and 409 when a mirror configuration already exists.
+//!
+//! The token has every OAuth scope unless removed from `QuayState::scopes`, requests needing
+//! a missing scope are answered with 403, and the permissions reported for the organizations
+//! follow the scopes, like Quay does.
+//!
+//! `FakeQuay::fail_next` answers the next matching request with an error instead of executing it.
+
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use openssl::pkey::PKey;
+use openssl::ssl::{Ssl, SslAcceptor, SslMethod, SslVerifyMode};
+use openssl::x509::X509;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::TcpListener;
+use tokio_openssl::SslStream;
+
+const SERVER_CERT: &[u8] = include_bytes!("../fixtures/tls/server.crt");
+const SERVER_KEY: &[u8] = include_bytes!("../fixtures/tls/server.key");
+const CA_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.crt");
+
+/// How the fake server is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Http,
+    /// TLS with a certificate signed by the test CA
+    Https,
+    /// TLS requiring a client certificate signed by the test CA
+    MutualTls,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Organization {
+    pub email: String,
+    /// Robot descriptions, by short name
+    pub robots: BTreeMap<String, String>,
+    pub teams: BTreeMap<String, Team>,
+    pub repositories: BTreeMap<String, Repository>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Team {
+    pub description: String,
+    pub role: String,
+    /// Users, and robots as '<organization>+<robot>'
+    pub members: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Repository {
+    pub description: String,
+    pub is_public: bool,
+    pub state: String,
+    /// Roles of users and robots, robots as '<organization>+<robot>'
+    pub user_permissions: BTreeMap<String, String>,
+    pub team_permissions: BTreeMap<String, String>,
+    pub mirror: Option<Value>,
+    /// Password of the external registry, not returned by Quay with the mirror configuration
+    pub mirror_password: Option<String>,
+}
+
+/// OAuth scopes of a Quay token.
+pub const SCOPES: [&str; 6] = [
+    "org:admin",
+    "repo:admin",
+    "repo:create",
+    "repo:read",
+    "repo:write",
+    "user:read",
+];
+
+#[derive(Debug, Default)]
+pub struct QuayState {
+    pub organizations: BTreeMap<String, Organization>,
+    /// OAuth scopes of the token
+    pub scopes: BTreeSet<String>,
+    /// Every request received, as '<method> <path>'
+    pub requests: Vec<String>,
+    pub faults: Vec<Fault>,
+}
+
+/// Error returned once, instead of executing the request.
+#[derive(Debug, Clone)]
+pub struct Fault {
+    /// '<method> <path>', the path relative to the API, e.g. 'POST repository'
+    pub request: String,
+    pub status: StatusCode,
+    pub message: String,
+    /// Headers of the response, e.g. Retry-After
+    pub headers: Vec<(String, String)>,
+}
+
+impl QuayState {
+    fn take_fault(&mut self, method: &Method, path: &str) -> Option<Fault> {
+        let request = format!("{} {}", method, path);
+        let i = self.faults.iter().position(|f| f.request == request)?;
+        Some(self.faults.remove(i))
+    }
+}
+
+/// Fake Quay server, running until the test ends.
+pub struct FakeQuay {
+    port: u16,
+    transport: Transport,
+    prefix: String,
+    state: Arc<Mutex<QuayState>>,
+}
+
+impl FakeQuay {
+    /// Starts a server accepting only requests authenticated with `token`.
+    pub async fn start(token: &str) -> FakeQuay {
+        Self::serve(token, Transport::Https, "").await
+    }
+
+    /// Starts a plain HTTP server serving the API under `prefix`, like a Quay behind a
+    /// path-prefixed reverse proxy.
+    pub async fn start_http(token: &str, prefix: &str) -> FakeQuay {
+        Self::serve(token, Transport::Http, prefix).await
+    }
+
+    /// Starts a server rejecting the connections without a valid client certificate.
+    pub async fn start_mutual_tls(token: &str) -> FakeQuay {
+        Self::serve(token, Transport::MutualTls, "").await
+    }
+
+    async fn serve(token: &str, transport: Transport, prefix: &str) -> FakeQuay {
+        let acceptor = (transport != Transport::Http).then(|| {
+            let mut builder =
+                SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+            builder
+                .set_certificate(&X509::from_pem(SERVER_CERT).unwrap())
+                .unwrap();
+            builder
+                .set_private_key(&PKey::private_key_from_pem(SERVER_KEY).unwrap())
+                .unwrap();
+            if transport == Transport::MutualTls {
+                builder.set_ca_file(CA_FILE).unwrap();
+                builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+            }
+            Arc::new(builder.build())
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(QuayState {
+            scopes: SCOPES.iter().map(|scope| scope.to_string()).collect(),
+            ..Default::default()
+        }));
+
+        let server_state = state.clone();
+        let token = format!("Bearer {}", token);
+        let api_prefix = format!("{}/api/v1/", prefix);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(_) => continue,
+                };
+                let acceptor = acceptor.clone();
+                let state = server_state.clone();
+                let token = token.clone();
+                let api_prefix = api_prefix.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| {
+                        handle(request, state.clone(), token.clone(), api_prefix.clone())
+                    });
+                    match acceptor {
+                        Some(acceptor) => {
+                            let ssl = Ssl::new(acceptor.context()).unwrap();
+                            let mut stream = SslStream::new(ssl, stream).unwrap();
+                            if Pin::new(&mut stream).accept().await.is_ok() {
+                                let _ = Http::new().serve_connection(stream, service).await;
+                            }
+                        }
+                        None => {
+                            let _ = Http::new().serve_connection(stream, service).await;
+                        }
+                    }
+                });
+            }
+        });
+
+        FakeQuay {
+            port,
+            transport,
+            prefix: prefix.to_string(),
+            state,
+        }
+    }
+
+    /// Quay endpoint to use in the yaml files: the host and port for the TLS servers,
+    /// the full URL for the plain HTTP one.
+    pub fn endpoint(&self) -> String {
+        match self.transport {
+            Transport::Http => format!("http://127.0.0.1:{}{}", self.port, self.prefix),
+            _ => format!("127.0.0.1:{}", self.port),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, QuayState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn organization(&self, name: &str) -> Option<Organization> {
+        self.state().organizations.get(name).cloned()
+    }
+
+    /// Removes an OAuth scope from the token.
+    pub fn remove_scope(&self, scope: &str) {
+        self.state().scopes.remove(scope);
+    }
+
+    /// Answers the next request '<method> <path>' with `status` and `message`.
+    pub fn fail_next(&self, request: &str, status: StatusCode, message: &str) {
+        self.state().faults.push(Fault {
+            request: request.to_string(),
+            status,
+            message: message.to_string(),
+            headers: Vec::new(),
+        });
+    }
+
+    /// Number of received requests with this method.
+    pub fn count_requests(&self, method: &str) -> usize {
+        self.state()
+            .requests
+            .iter()
+            .filter(|r| r.starts_with(&format!("{} ", method)))
+            .count()
+    }
+}
+
+async fn handle(
+    request: Request<Body>,
+    state: Arc<Mutex<QuayState>>,
+    token: String,
+    api_prefix: String,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let path = percent_decode(parts.uri.path());
+
+    let mut state = state.lock().unwrap();
+    // Logged as sent, so escaped path segments can be told apart
+    state
+        .requests
+        .push(format!("{} {}", parts.method, parts.uri.path()));
+
+    let authorized = parts
+        .headers
+        .get("Authorization")
+        .is_some_and(|value| value.as_bytes() == token.as_bytes());
+
+    let mut headers = Vec::new();
+    let (status, response) = match path.strip_prefix(&api_prefix) {
+        _ if !authorized => error(StatusCode::UNAUTHORIZED, "Invalid or missing token"),
+        Some(path) => match state.take_fault(&parts.method, path.trim_end_matches('/')) {
+            Some(fault) => {
+                headers = fault.headers;
+                error(fault.status, &fault.message)
+            }
+            None => route(
+                &mut state,
+                &parts.method,
+                path.trim_end_matches('/'),
+                parts.uri.query().unwrap_or_default(),
+                body,
+            ),
+        },
+        None => error(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    let body = match status {
+        StatusCode::NO_CONTENT => Body::empty(),
+        _ => Body::from(response.to_string()),
+    };
+    let mut response = Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json");
+    for (name, value) in headers {
+        response = response.header(name, value);
+    }
+    Ok(response.body(body).unwrap())
+}
+
+/// Decodes the percent-encoded characters of the path, like Quay does before routing.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = path
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Value) {
+    (
+        status,
+        json!({ "status": status.as_u16(), "error_message": message, "detail": message }),
+    )
+}
+
+fn created() -> (StatusCode, Value) {
+    (StatusCode::CREATED, json!("Created"))
+}
+
+fn deleted() -> (StatusCode, Value) {
+    (StatusCode::NO_CONTENT, Value::Null)
+}
+
+fn text(body: &Value, key: &str) -> String {
+    body[key].as_str().unwrap_or_default().to_string()
+}
+
+fn route(
+    state: &mut QuayState,
+    method: &Method,
+    path: &str,
+    query: &str,
+    body: Value,
+) -> (StatusCode, Value) {
+    let segments: Vec<&str> = path.split('/').collect();
+
+    let scope = match (method, segments.as_slice()) {
+        (&Method::GET, ["user"]) => "user:read",
+        (&Method::GET, ["organization", _]) | (&Method::GET, ["repository"]) => "repo:read",
+        (_, ["organization", _, ..]) => "org:admin",
+        (&Method::POST, ["repository"]) => "repo:create",
+        (&Method::GET, ["repository", ..]) if !path.ends_with("/mirror") => "repo:read",
+        (_, ["repository", ..]) => "repo:admin",
+        _ => "repo:read",
+    };
+    if !state.scopes.contains(scope) {
+        return error(StatusCode::FORBIDDEN, "Unauthorized");
+    }
+    let is_admin = state.scopes.contains("org:admin");
+    let can_create_repo = state.scopes.contains("repo:create");
+
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["user"]) => {
+            let organizations: Vec<Value> = state
+                .organizations
+                .keys()
+                .map(|name| {
+                    json!({
+                        "name": name,
+                        "is_org_admin": is_admin,
+                        "can_create_repo": can_create_repo,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                json!({ "username": "admin", "organizations": organizations }),
+            )
+        }
+
+        (&Method::POST, ["organization"]) => {
+            let name = text(&body, "name");
+            if state.organizations.contains_key(&name) {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "A user or organization with this name already exists",
+                );
+            }
+            let organization = Organization {
+                email: text(&body, "email"),
+                ..Default::default()
+            };
+            state.organizations.insert(name, organization);
+            created()
+        }
+        (_, ["organization", org, rest @ ..]) => match state.organizations.get_mut(*org) {
+            Some(organization) => match (method, rest) {
+                (&Method::GET, []) => (
+                    StatusCode::OK,
+                    organization_json(org, organization, is_admin),
+                ),
+                (&Method::DELETE, []) => {
+                    state.organizations.remove(*org);
+                    deleted()
+                }
+                (_, ["robots", ..]) => robots(organization, org, method, &rest[1..], body),
+                (_, ["team", team, members @ ..]) => {
+                    teams(organization, org, method, team, members, body)
+                }
+                _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+            },
+            None => error(StatusCode::NOT_FOUND, "Organization not found"),
+        },
+
+        (&Method::GET, ["repository"]) => {
+            let namespace = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("namespace="))
+                .unwrap_or_default();
+            let repositories: Vec<Value> = state
+                .organizations
+                .get(namespace)
+                .map(|organization| {
+                    organization
+                        .repositories
+                        .iter()
+                        .map(|(name, repo)| {
+                            json!({
+                                "namespace": namespace,
+                                "name": name,
+                                "description": repo.description,
+                                "is_public": repo.is_public,
+                                "state": repo.state,
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            (StatusCode::OK, json!({ "repositories": repositories }))
+        }
+        (&Method::POST, ["repository"]) => {
+            let namespace = text(&body, "namespace");
+            let name = text(&body, "repository");
+            let organization = match state.organizations.get_mut(&namespace) {
+                Some(organization) => organization,
+                None => return error(StatusCode::NOT_FOUND, "Namespace not found"),
+            };
+            if organization.repositories.contains_key(&name) {
+                return error(StatusCode::BAD_REQUEST, "Repository already exists");
+            }
+            let repository = Repository {
+                description: text(&body, "description"),
+                is_public: text(&body, "visibility") != "private",
+                state: "NORMAL".to_string(),
+                ..Default::default()
+            };
+            organization.repositories.insert(name, repository);
+            created()
+        }
+        (_, ["repository", namespace, ..]) => {
+            let rest = &path["repository/".len() + namespace.len() + 1..];
+            match state.organizations.get_mut(*namespace) {
+                Some(organization) => repository(organization, namespace, method, rest, body),
+                None => error(StatusCode::NOT_FOUND, "Namespace not found"),
+            }
+        }
+
+        _ => error(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+fn organization_json(name: &str, organization: &Organization, is_admin: bool) -> Value {
+    let teams: serde_json::Map<String, Value> = organization
+        .teams
+        .iter()
+        .map(|(team_name, team)| {
+            (
+                team_name.clone(),
+                json!({
+                    "name": team_name,
+                    "description": team.description,
+                    "role": team.role,
+                    "member_count": team.members.len(),
+                }),
+            )
+        })
+        .collect();
+    json!({ "name": name, "email": organization.email, "is_admin": is_admin, "teams": teams })
+}
+
+fn robots(
+    organization: &mut Organization,
+    org: &str,
+    method: &Method,
+    rest: &[&str],
+    body: Value,
+) -> (StatusCode, Value) {
+    match (method, rest) {
+        (&Method::GET, []) => {
+            let robots: Vec<Value> = organization
+                .robots
+                .iter()
+                .map(|(name, description)| {
+                    json!({ "name": format!("{}+{}", org, name), "description": description })
+                })
+                .collect();
+            (StatusCode::OK, json!({ "robots": robots }))
+        }
+        (&Method::PUT, [robot]) => {
+            if organization.robots.contains_key(*robot) {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Existing robot with name: {}+{}", org, robot),
+                );
+            }
+            organization
+                .robots
+                .insert(robot.to_string(), text(&body, "description"));
+            (
+                StatusCode::CREATED,
+                json!({ "name": format!("{}+{}", org, robot), "token": "robot-token" }),
+            )
+        }
+        (&Method::DELETE, [robot]) => match organization.robots.remove(*robot) {
+            Some(_) => {
+                let full_name = format!("{}+{}", org, robot);
+                for team in organization.teams.values_mut() {
+                    team.members.remove(&full_name);
+                }
+                for repo in organization.repositories.values_mut() {
+                    repo.user_permissions.remove(&full_name);
+                }
+                deleted()
+            }
+            None => error(StatusCode::NOT_FOUND, "Robot not found"),
+        },
+        _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
+
+/// True if the robot exists, for names as '<organization>+<robot>'. Users always exist.
+fn member_exists(organization: &Organization, org: &str, name: &str) -> bool {
+    match name.strip_prefix(&format!("{}+", org)) {
+        Some(robot) => organization.robots.contains_key(robot),
+        None => !name.contains('+'),
+    }
+}
+
+fn teams(
+    organization: &mut Organization,
+    org: &str,
+    method: &Method,
+    name: &str,
+    rest: &[&str],
+    body: Value,
+) -> (StatusCode, Value) {
+    match (method, rest) {
+        (&Method::PUT, []) => {
+            let team = organization.teams.entry(name.to_string()).or_default();
+            team.description = text(&body, "description");
+            team.role = text(&body, "role");
+            (
+                StatusCode::OK,
+                json!({ "name": name, "description": team.description, "role": team.role }),
+            )
+        }
+        (&Method::DELETE, []) => match organization.teams.remove(name) {
+            Some(_) => {
+                for repo in organization.repositories.values_mut() {
+                    repo.team_permissions.remove(name);
+                }
+                deleted()
+            }
+            None => error(StatusCode::NOT_FOUND, "Team not found"),
+        },
+        (_, ["members", ..]) => {
+            let exists = rest
+                .get(1)
+                .map(|member| member_exists(organization, org, member));
+            let team = match organization.teams.get_mut(name) {
+                Some(team) => team,
+                None => return error(StatusCode::NOT_FOUND, "Team not found"),
+            };
+            match (method, rest.get(1)) {
+                (&Method::GET, None) => {
+                    let members: Vec<Value> = team
+                        .members
+                        .iter()
+                        .map(|member| {
+                            let is_robot = member.contains('+');
+                            json!({
+                                "name": member,
+                                "kind": if is_robot { "robot" } else { "user" },
+                                "is_robot": is_robot,
+                            })
+                        })
+                        .collect();
+                    (StatusCode::OK, json!({ "name": name, "members": members }))
+                }
+                (&Method::PUT, Some(member)) if exists == Some(true) => {
+                    team.members.insert(member.to_string());
+                    (StatusCode::OK, json!({ "name": member }))
+                }
+                (&Method::PUT, Some(member)) => error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Unknown user or robot: {}", member),
+                ),
+                (&Method::DELETE, Some(member)) => match team.members.remove(*member) {
+                    true => deleted(),
+                    false => error(StatusCode::NOT_FOUND, "Member not found"),
+                },
+                _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+            }
+        }
+        _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
+
+/// Repository endpoints, `rest` is '<repository>[/<action>]', repository names can contain '/'.
+fn repository(
+    organization: &mut Organization,
+    org: &str,
+    method: &Method,
+    rest: &str,
+    body: Value,
+) -> (StatusCode, Value) {
+    let (name, action) = [
+        "/permissions/",
+        "/mirror",
+        "/changestate",
+        "/changevisibility",
+    ]
+    .iter()
+    .find_map(|marker| rest.find(marker).map(|i| (&rest[..i], &rest[i + 1..])))
+    .unwrap_or((rest, ""));
+    let action: Vec<&str> = action.split('/').filter(|s| !s.is_empty()).collect();
+
+    let robot_exists = match action.as_slice() {
+        ["permissions", "user", member] => member_exists(organization, org, member),
+        ["mirror"] => {
+            let robot = body["robot_username"].as_str().unwrap_or_default();
+            member_exists(organization, org, robot) && robot.contains('+')
+        }
+        _ => true,
+    };
+    let team_exists = match action.as_slice() {
+        ["permissions", "team", team] => organization.teams.contains_key(*team),
+        _ => true,
+    };
+
+    let repo = match organization.repositories.get_mut(name) {
+        Some(repo) => repo,
+        None => return error(StatusCode::NOT_FOUND, "Repository not found"),
+    };
+
+    match (method, action.as_slice()) {
+        (&Method::PUT, []) => {
+            repo.description = text(&body, "description");
+            (StatusCode::OK, json!({ "success": true }))
+        }
+        (&Method::DELETE, []) => {
+            organization.repositories.remove(name);
+            deleted()
+        }
+        (&Method::POST, ["changevisibility"]) => {
+            repo.is_public = text(&body, "visibility") != "private";
+            (StatusCode::OK, json!({ "success": true }))
+        }
+        (&Method::PUT, ["changestate"]) => {
+            repo.state = text(&body, "state");
+            (StatusCode::OK, json!({ "success": true }))
+        }
+
+        (&Method::GET, ["permissions", kind]) => {
+            let permissions = match *kind {
+                "user" => &repo.user_permissions,
+                _ => &repo.team_permissions,
+            };
+            let permissions: serde_json::Map<String, Value> = permissions
+                .iter()
+                .map(|(name, role)| {
+                    (
+                        name.clone(),
+                        json!({ "name": name, "role": role, "is_robot": name.contains('+') }),
+                    )
+                })
+                .collect();
+            (StatusCode::OK, json!({ "permissions": permissions }))
+        }
+        (&Method::PUT, ["permissions", _, name]) if !robot_exists || !team_exists => error(
+            StatusCode::BAD_REQUEST,
+            &format!("Invalid user, robot or team: {}", name),
+        ),
+        (&Method::PUT, ["permissions", kind, name]) => {
+            let role = text(&body, "role");
+            let permissions = match *kind {
+                "user" => &mut repo.user_permissions,
+                _ => &mut repo.team_permissions,
+            };
+            permissions.insert(name.to_string(), role.clone());
+            (StatusCode::OK, json!({ "name": name, "role": role }))
+        }
+        (&Method::DELETE, ["permissions", kind, name]) => {
+            let permissions = match *kind {
+                "user" => &mut repo.user_permissions,
+                _ => &mut repo.team_permissions,
+            };
+            match permissions.remove(*name) {
+                Some(_) => deleted(),
+                None => error(StatusCode::NOT_FOUND, "Permission not found"),
+            }
+        }
+
+        (&Method::GET, ["mirror"]) => match &repo.mirror {
+            Some(mirror) => (StatusCode::OK, mirror.clone()),
+            None => error(StatusCode::NOT_FOUND, "Mirror configuration not found"),
+        },
+        (&Method::POST | &Method::PUT, ["mirror"]) if repo.state != "MIRROR" => {
+            error(StatusCode::BAD_REQUEST, "Repository is not in mirror state")
+        }
+        (&Method::POST | &Method::PUT, ["mirror"]) if !robot_exists => {
+            error(StatusCode::BAD_REQUEST, "Invalid robot username")
+        }
+        (&Method::POST, ["mirror"]) if repo.mirror.is_some() => {
+            error(StatusCode::CONFLICT, "Mirror configuration already exists")
+        }
+        (&Method::POST | &Method::PUT, ["mirror"]) => {
+            let mut mirror = body;
+            // Quay enables the mirror when is_enabled is not sent
+            if mirror["is_enabled"].is_null() {
+                mirror["is_enabled"] = json!(true);
+            }
+            if let Some(mirror) = mirror.as_object_mut() {
+                repo.mirror_password = mirror
+                    .remove("external_registry_password")
+                    .and_then(|password| password.as_str().map(str::to_string));
+            }
+            repo.mirror = Some(mirror);
+            created()
+        }
+
+        _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
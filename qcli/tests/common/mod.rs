@@ -0,0 +1,191 @@
+//! Helpers shared by the integration tests: a fake Quay server and a temporary working
+//! directory with the example yaml files, where qcli is executed.
+
+#![allow(dead_code, unused_imports)]
+
+pub mod fake_quay;
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+pub use fake_quay::FakeQuay;
+
+/// Token accepted by the fake Quay server.
+pub const TOKEN: &str = "fake-quay-token";
+
+/// Password of the mirror users declared in the example yaml files.
+pub const MIRROR_PASSWORD: &str = "mirror-password";
+
+/// Passphrase of the login store, set in QCLI_LOGIN_PASSPHRASE.
+pub const PASSPHRASE: &str = "login-store-passphrase";
+
+/// Path of a file of tests/fixtures/tls.
+pub fn tls_fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Working directory of a qcli run.
+pub struct Workspace {
+    dir: TempDir,
+    /// Trust the test CA with --ca-bundle
+    ca_bundle: bool,
+    /// Env variables of the qcli process
+    env: Vec<(String, String)>,
+}
+
+impl Workspace {
+    /// Copies the example yaml files into `yaml-files`, pointing them to `quay_endpoint`.
+    /// All the example files are copied if `files` is empty.
+    pub fn new(quay_endpoint: &str, files: &[&str]) -> Workspace {
+        let dir = TempDir::new().unwrap();
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("yaml-files");
+        fs::create_dir(dir.path().join("yaml-files")).unwrap();
+
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+            if !files.is_empty() && !files.contains(&file_name.as_str()) {
+                continue;
+            }
+
+            let yaml: String = fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .map(|line| match line.starts_with("quay_endpoint:") {
+                    true => format!("quay_endpoint: \"{}\"\n", quay_endpoint),
+                    false => format!("{}\n", line),
+                })
+                .collect();
+            fs::write(dir.path().join("yaml-files").join(file_name), yaml).unwrap();
+        }
+
+        Workspace {
+            dir,
+            ca_bundle: true,
+            env: vec![("QCLI_LOGIN_PASSPHRASE".to_string(), PASSPHRASE.to_string())],
+        }
+    }
+
+    /// Sets an env variable of the next qcli runs.
+    pub fn with_env(mut self, key: &str, value: &str) -> Workspace {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Runs qcli without --ca-bundle, so the fake Quay certificate is not trusted.
+    pub fn without_ca_bundle(mut self) -> Workspace {
+        self.ca_bundle = false;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Config directory of qcli, set with XDG_CONFIG_HOME.
+    pub fn config_dir(&self) -> PathBuf {
+        self.path().join("config").join("qcli")
+    }
+
+    /// Default login file, in the config directory.
+    pub fn login_file(&self) -> PathBuf {
+        self.config_dir().join("login.yaml")
+    }
+
+    /// Replaces `from` with `to` in a copied yaml file.
+    pub fn edit_yaml(&self, file: &str, from: &str, to: &str) {
+        let path = self.path().join("yaml-files").join(file);
+        let yaml = fs::read_to_string(&path).unwrap();
+        assert!(yaml.contains(from), "'{}' not found in {}", from, file);
+        fs::write(path, yaml.replace(from, to)).unwrap();
+    }
+
+    /// Writes a yaml file in `yaml-files`.
+    pub fn write_yaml(&self, file: &str, yaml: &str) {
+        fs::write(self.path().join("yaml-files").join(file), yaml).unwrap();
+    }
+
+    /// Writes the login file as it is.
+    pub fn write_login_yaml(&self, login: &str) {
+        fs::create_dir_all(self.config_dir()).unwrap();
+        fs::write(self.login_file(), login).unwrap();
+    }
+
+    /// Writes the login file with the token of the endpoint and the mirror passwords.
+    pub fn write_login(&self, quay_endpoint: &str, token: &str, mirror_passwords: bool) {
+        let mut login = format!(
+            "quay_endpoint_login:\n- quay_endpoint: \"{}\"\n  quay_token: \"{}\"\n",
+            quay_endpoint, token
+        );
+        if mirror_passwords {
+            login.push_str("mirror_repository:\n");
+            for (organization, username) in [("exampleorg", "valeidm"), ("exampleorg2", "hola")] {
+                login.push_str(&format!(
+                    "- organization: {}\n  repository: alpine\n  ext_registry_username: {}\n  ext_registry_password: {}\n",
+                    organization, username, MIRROR_PASSWORD
+                ));
+            }
+        }
+
+        self.write_login_yaml(&login);
+    }
+
+    /// Runs qcli in the workspace, writing `stdin` to its standard input.
+    pub async fn qcli(&self, args: &[&str], stdin: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_qcli"))
+            .current_dir(self.path())
+            .args([
+                "--dir",
+                "yaml-files",
+                "--rate-limit",
+                "1000",
+                "--retry-delay",
+                "10",
+            ])
+            .args(match self.ca_bundle {
+                true => vec!["--ca-bundle".to_string(), tls_fixture("ca.crt")],
+                false => vec![],
+            })
+            .args(args)
+            .env_remove("RUST_LOG")
+            .env("XDG_CONFIG_HOME", self.path().join("config"))
+            .env("HOME", self.path())
+            .env_remove("QCLI_LOGIN_NEW_PASSPHRASE")
+            .env_remove("QCLI_CONFIG")
+            .env_remove("QCLI_LOGIN_FILE")
+            .env_remove("QCLI_STRICT")
+            .envs(self.env.iter().cloned())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut input = child.stdin.take().unwrap();
+        input.write_all(stdin.as_bytes()).await.unwrap();
+        drop(input);
+
+        child.wait_with_output().await.unwrap()
+    }
+
+    /// Runs qcli with the json run report, returning the exit status and the report.
+    pub async fn qcli_report(&self, args: &[&str]) -> (bool, Value) {
+        let args = [&["--output", "json"], args].concat();
+        let output = self.qcli(&args, "").await;
+        let report = serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+            panic!(
+                "invalid json report ({}): {}\n{}",
+                e,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            )
+        });
+        (output.status.success(), report)
+    }
+}
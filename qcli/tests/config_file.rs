@@ -0,0 +1,149 @@
+mod common;
+
+use common::{tls_fixture, FakeQuay, Workspace, TOKEN};
+use std::fs;
+use std::path::Path;
+
+/// Moves the login file written by the workspace to `to`.
+fn move_login_file(workspace: &Workspace, to: &Path) {
+    fs::create_dir_all(to.parent().unwrap()).unwrap();
+    fs::rename(workspace.login_file(), to).unwrap();
+}
+
+#[tokio::test]
+async fn login_file_is_read_from_option_env_or_legacy_location() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let login_file = workspace.path().join("secrets").join("quay-login.yaml");
+    move_login_file(&workspace, &login_file);
+
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&workspace.login_file().display().to_string()),
+        "{}",
+        stderr
+    );
+
+    let output = workspace
+        .qcli(
+            &[
+                "--login-file",
+                login_file.to_str().unwrap(),
+                "login",
+                "--show",
+            ],
+            "",
+        )
+        .await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&quay.endpoint()));
+
+    // .qcli/login.yaml of the current directory is still used by default
+    let legacy_login_file = workspace.path().join(".qcli").join("login.yaml");
+    fs::create_dir(workspace.path().join(".qcli")).unwrap();
+    fs::rename(&login_file, &legacy_login_file).unwrap();
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(output.status.success());
+
+    fs::rename(&legacy_login_file, &login_file).unwrap();
+    let workspace = workspace.with_env("QCLI_LOGIN_FILE", login_file.to_str().unwrap());
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(output.status.success());
+}
+
+#[tokio::test]
+async fn login_file_of_config_file_is_relative_to_it() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    move_login_file(
+        &workspace,
+        &workspace.config_dir().join("secrets").join("login.yaml"),
+    );
+    fs::write(
+        workspace.config_dir().join("qcli.toml"),
+        "login_file = \"secrets/login.yaml\"\n",
+    )
+    .unwrap();
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg").is_some());
+}
+
+#[tokio::test]
+async fn options_override_env_variables_overriding_config_file() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    fs::write(
+        workspace.config_dir().join("qcli.toml"),
+        "output = \"json\"\ntimeout = 10\nconcurrency = 4\n",
+    )
+    .unwrap();
+
+    let output = workspace.qcli(&["create"], "").await;
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["failed"], 0);
+
+    let workspace = workspace.with_env("QCLI_OUTPUT", "junit");
+    let output = workspace.qcli(&["create"], "").await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("<testsuites"));
+
+    let output = workspace.qcli(&["--output", "text", "create"], "").await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Execution terminated."));
+}
+
+#[tokio::test]
+async fn invalid_config_file_is_rejected() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    fs::write(
+        workspace.config_dir().join("qcli.toml"),
+        "timeout = 10\nrate_limits = 20\n",
+    )
+    .unwrap();
+
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("qcli.toml"), "{}", stderr);
+    assert!(stderr.contains("rate_limits"), "{}", stderr);
+
+    // An explicit config file must exist
+    let output = workspace
+        .qcli(&["--config", "missing.toml", "check"], "")
+        .await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.toml"));
+}
+
+#[tokio::test]
+async fn certificates_of_config_file_are_relative_to_it() {
+    let quay = FakeQuay::start_mutual_tls(TOKEN).await;
+    let workspace =
+        Workspace::new(&quay.endpoint(), &["example-organization3.yaml"]).without_ca_bundle();
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+    let tls_dir = workspace.config_dir().join("tls");
+    fs::create_dir(&tls_dir).unwrap();
+    for file in ["ca.crt", "client.crt", "client.key"] {
+        fs::copy(tls_fixture(file), tls_dir.join(file)).unwrap();
+    }
+    fs::write(
+        workspace.config_dir().join("qcli.toml"),
+        "ca_bundle = \"tls/ca.crt\"\nclient_cert = \"tls/client.crt\"\nclient_key = \"tls/client.key\"\n",
+    )
+    .unwrap();
+
+    // qcli runs in the workspace directory, not in the config directory
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg3").is_some());
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{FakeQuay, Workspace, MIRROR_PASSWORD, TOKEN};
+use std::fs;
+
+/// Quay endpoint of the yaml files, redirected by the contexts.
+const YAML_ENDPOINT: &str = "quay.example.com";
+
+const STAGING_TOKEN: &str = "fake-staging-token";
+
+/// Login file with a staging and a prod context, and a mirror password shared by both.
+fn write_contexts(workspace: &Workspace, staging: &FakeQuay, prod: &FakeQuay) {
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login: []
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password: {}
+current_context: staging
+contexts:
+- name: staging
+  endpoints:
+    {}: \"{}\"
+  quay_endpoint_login:
+  - quay_endpoint: \"{}\"
+    quay_token: {}
+- name: prod
+  endpoints:
+    {}: \"{}\"
+  quay_endpoint_login:
+  - quay_endpoint: \"{}\"
+    quay_token: {}
+",
+        MIRROR_PASSWORD,
+        YAML_ENDPOINT,
+        staging.endpoint(),
+        staging.endpoint(),
+        STAGING_TOKEN,
+        YAML_ENDPOINT,
+        prod.endpoint(),
+        prod.endpoint(),
+        TOKEN
+    ));
+}
+
+#[tokio::test]
+async fn context_redirects_yaml_endpoints_with_its_tokens() {
+    let staging = FakeQuay::start(STAGING_TOKEN).await;
+    let prod = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(YAML_ENDPOINT, &["example-organization.yaml"]);
+    write_contexts(&workspace, &staging, &prod);
+
+    // current_context of the login file
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(staging.organization("exampleorg").is_some());
+    assert!(prod.organization("exampleorg").is_none());
+
+    let (success, report) = workspace
+        .qcli_report(&["--context", "prod", "create"])
+        .await;
+    assert!(success, "{}", report);
+    let alpine = &prod.organization("exampleorg").unwrap().repositories["alpine"];
+    assert_eq!(alpine.mirror_password.as_deref(), Some(MIRROR_PASSWORD));
+
+    let output = workspace
+        .qcli(&["--context", "missing", "create"], "")
+        .await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Context 'missing' not found"), "{}", stdout);
+}
+
+#[tokio::test]
+async fn context_use_changes_the_current_context() {
+    let staging = FakeQuay::start(STAGING_TOKEN).await;
+    let prod = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(YAML_ENDPOINT, &["example-organization.yaml"]);
+    write_contexts(&workspace, &staging, &prod);
+
+    let output = workspace.qcli(&["context", "use", "prod"], "").await;
+    assert!(output.status.success());
+    // A plain text login file stays in plain text, readable by the owner only
+    let login = fs::read_to_string(workspace.login_file()).unwrap();
+    assert!(login.contains("current_context: prod"), "{}", login);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(workspace.login_file())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let output = workspace.qcli(&["context", "list"], "").await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  default\n"), "{}", stdout);
+    assert!(stdout.contains("  staging\n"), "{}", stdout);
+    assert!(stdout.contains("* prod\n"), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("    {} -> {}\n", YAML_ENDPOINT, prod.endpoint())),
+        "{}",
+        stdout
+    );
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(prod.organization("exampleorg").is_some());
+    assert!(staging.organization("exampleorg").is_none());
+
+    let output = workspace.qcli(&["context", "use", "missing"], "").await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Available contexts: default, staging, prod"),
+        "{}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn login_saves_the_tokens_of_the_context() {
+    let prod = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(YAML_ENDPOINT, &["example-organization.yaml"]);
+    workspace.write_login(YAML_ENDPOINT, "unused-token", true);
+
+    let mapping = format!("{}={}", YAML_ENDPOINT, prod.endpoint());
+    let output = workspace
+        .qcli(&["context", "set", "prod", "--endpoint", &mapping], "")
+        .await;
+    assert!(output.status.success());
+
+    // Only the token of the prod endpoint is missing, mirror passwords are shared
+    let output = workspace
+        .qcli(
+            &["--context", "prod", "login", "--merge"],
+            &format!("{}\n", TOKEN),
+        )
+        .await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&prod.endpoint()), "{}", stdout);
+
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    let login = String::from_utf8_lossy(&output.stdout);
+    assert!(login.contains("name: prod"), "{}", login);
+    assert!(login.contains("quay_token: \"********\""), "{}", login);
+
+    let (success, report) = workspace
+        .qcli_report(&["--context", "prod", "create"])
+        .await;
+    assert!(success, "{}", report);
+    assert!(prod.organization("exampleorg").is_some());
+}
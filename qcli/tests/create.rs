@@ -0,0 +1,145 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+
+#[tokio::test]
+async fn create_configures_example_organizations() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    assert_eq!(report["skipped"], 0);
+    assert_eq!(quay.state().organizations.len(), 6);
+
+    let org = quay.organization("exampleorg").unwrap();
+    assert_eq!(org.robots.keys().collect::<Vec<_>>(), ["reader", "writer"]);
+
+    let team = &org.teams["maintainers"];
+    assert_eq!(team.role, "member");
+    assert!(team.members.contains("valeidm"));
+    assert!(team.members.contains("exampleorg+writer"));
+
+    let alpine = &org.repositories["alpine"];
+    assert!(alpine.is_public);
+    assert_eq!(alpine.state, "MIRROR");
+    assert_eq!(alpine.user_permissions["exampleorg+reader"], "read");
+    assert_eq!(alpine.user_permissions["exampleorg+writer"], "write");
+    assert_eq!(alpine.user_permissions["valeidm"], "admin");
+    assert_eq!(alpine.team_permissions["maintainers"], "admin");
+    let mirror = alpine.mirror.as_ref().unwrap();
+    assert_eq!(mirror["external_reference"], "quay.io/libpod/alpine");
+    assert_eq!(mirror["robot_username"], "exampleorg+writer");
+    assert_eq!(mirror["external_registry_username"], "valeidm");
+
+    let nested = &org.repositories["nested/demorepo"];
+    assert_eq!(nested.user_permissions["exampleorg+writer"], "write");
+    assert!(nested.mirror.is_none());
+
+    let private = &quay.organization("exampleorg4").unwrap().repositories["alpine"];
+    assert!(!private.is_public);
+}
+
+#[tokio::test]
+async fn create_twice_succeeds() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let (success, _) = workspace.qcli_report(&["create"]).await;
+    assert!(success);
+    let first = quay.organization("exampleorg").unwrap();
+    quay.state()
+        .organizations
+        .get_mut("exampleorg")
+        .unwrap()
+        .repositories
+        .get_mut("alpine")
+        .unwrap()
+        .is_public = false;
+
+    // Existing objects are answered with 400 and the mirror configuration with 409
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    let status = |object_type: &str| {
+        report["operations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|o| o["object_type"] == object_type)
+            .map(|o| o["status"].as_str().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(status("organization"), ["unchanged"]);
+    assert_eq!(status("robot"), ["unchanged", "unchanged"]);
+    // Existing repositories are updated instead
+    assert_eq!(
+        status("repository"),
+        ["succeeded", "succeeded", "succeeded"]
+    );
+    assert_eq!(report["unchanged"], 3);
+    let second = quay.organization("exampleorg").unwrap();
+    assert_eq!(first.robots, second.robots);
+    assert_eq!(
+        first.repositories.keys().collect::<Vec<_>>(),
+        second.repositories.keys().collect::<Vec<_>>()
+    );
+    assert!(second.repositories["alpine"].mirror.is_some());
+    assert!(second.repositories["alpine"].is_public);
+}
+
+#[tokio::test]
+async fn create_with_invalid_token_fails() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization3.yaml"]);
+    workspace.write_login(&quay.endpoint(), "wrong-token", true);
+
+    // The token is rejected by the preflight checks, before any write
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(!success);
+    assert_eq!(report["succeeded"], 0);
+    assert_eq!(report["failed"], 0);
+    let message = report["errors"][0]["message"].as_str().unwrap();
+    assert!(
+        message.starts_with("Preflight: Token rejected"),
+        "{}",
+        message
+    );
+    assert_eq!(quay.count_requests("POST"), 0);
+
+    let (success, report) = workspace.qcli_report(&["--skip-preflight", "create"]).await;
+
+    assert!(!success);
+    assert_eq!(report["succeeded"], 0);
+    assert_eq!(report["failed"], 1);
+    let operation = &report["operations"][0];
+    assert_eq!(operation["object_type"], "organization");
+    assert_eq!(operation["status_code"], 401);
+    assert!(quay.state().organizations.is_empty());
+}
+
+#[tokio::test]
+async fn create_through_path_prefixed_http_endpoint() {
+    let quay = FakeQuay::start_http(TOKEN, "/quay").await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    let org = quay.organization("exampleorg").unwrap();
+    assert!(org.repositories["nested/demorepo"]
+        .user_permissions
+        .contains_key("valeidm"));
+    assert!(quay.state().requests.contains(
+        &"PUT /quay/api/v1/repository/exampleorg/nested/demorepo/permissions/user/valeidm"
+            .to_string()
+    ));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{FakeQuay, Workspace, MIRROR_PASSWORD, TOKEN};
+use std::fs;
+
+#[tokio::test]
+async fn credentials_are_read_from_env_and_file() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"])
+        .with_env("QUAY_TOKEN_TEST", TOKEN);
+    let password_file = workspace.path().join("mirror-password");
+    fs::write(&password_file, format!("{}\n", MIRROR_PASSWORD)).unwrap();
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token_from:
+    env: QUAY_TOKEN_TEST
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password_from:
+    file: {}
+",
+        quay.endpoint(),
+        password_file.display()
+    ));
+
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(!log.contains("Missing mirror user password"), "{}", log);
+    assert!(!log.contains("plain text"), "{}", log);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let organization = quay.organization("exampleorg").unwrap();
+    assert_eq!(
+        organization.repositories["alpine"]
+            .mirror_password
+            .as_deref(),
+        Some(MIRROR_PASSWORD)
+    );
+}
+
+#[tokio::test]
+async fn credential_command_is_run_once_and_first_line_is_used() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token_from:
+    command: [\"sh\", \"-c\", \"echo run >> calls; printf '{}\\\\nlogin: someone\\\\n'\"]
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password: {}
+- organization: exampleorg2
+  repository: alpine
+  ext_registry_username: hola
+  ext_registry_password: {}
+",
+        quay.endpoint(),
+        TOKEN,
+        MIRROR_PASSWORD,
+        MIRROR_PASSWORD
+    ));
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg").is_some());
+    assert!(quay.organization("exampleorg2").is_some());
+
+    // Both organizations are on the same endpoint
+    let calls = fs::read_to_string(workspace.path().join("calls")).unwrap();
+    assert_eq!(calls.lines().count(), 1);
+}
+
+#[tokio::test]
+async fn organization_is_ignored_when_credential_source_fails() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token_from:
+    command: [\"sh\", \"-c\", \"echo locked >&2; exit 1\"]
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password: {}
+",
+        quay.endpoint(),
+        MIRROR_PASSWORD
+    ));
+
+    let output = workspace.qcli(&["create"], "").await;
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(log.contains("Unable to read the token"), "{}", log);
+    assert!(log.contains("locked"), "{}", log);
+    assert!(quay.state().requests.is_empty());
+}
+
+#[tokio::test]
+async fn credential_sources_are_read_only_when_a_request_needs_them() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization3.yaml"]);
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token: {}
+- quay_endpoint: \"quay.other.example.com\"
+  quay_token_from:
+    command: [\"sh\", \"-c\", \"echo run >> calls; exit 1\"]
+",
+        quay.endpoint(),
+        TOKEN
+    ));
+
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(output.status.success());
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(output.status.success());
+    // The other endpoint is not used by the organizations
+    let output = workspace.qcli(&["plan"], "").await;
+    assert!(output.status.success());
+    let output = workspace
+        .qcli(&["export", "--endpoint", &quay.endpoint()], "")
+        .await;
+    assert!(output.status.success());
+
+    assert!(!workspace.path().join("calls").exists());
+}
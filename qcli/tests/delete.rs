@@ -0,0 +1,108 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+
+async fn created_workspace(quay: &FakeQuay) -> Workspace {
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    workspace
+}
+
+#[tokio::test]
+async fn delete_removes_all_organizations() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+
+    let (success, report) = workspace.qcli_report(&["delete"]).await;
+
+    assert!(success, "{}", report);
+    assert_eq!(report["succeeded"], 2);
+    assert!(quay.state().organizations.is_empty());
+}
+
+#[tokio::test]
+async fn delete_removes_selected_organization() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+
+    let (success, report) = workspace
+        .qcli_report(&["delete", "--org", "exampleorg2"])
+        .await;
+
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg2").is_none());
+    assert!(quay.organization("exampleorg").is_some());
+}
+
+#[tokio::test]
+async fn delete_removes_selected_objects() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+
+    let (success, report) = workspace
+        .qcli_report(&[
+            "delete",
+            "--org",
+            "exampleorg",
+            "--repository",
+            "nested/demorepo",
+            "--robot",
+            "reader",
+            "--team-member",
+            "maintainers:valeidm",
+            "--team-member",
+            "maintainers:writer",
+        ])
+        .await;
+
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    let org = quay.organization("exampleorg").unwrap();
+    assert!(!org.repositories.contains_key("nested/demorepo"));
+    assert!(org.repositories.contains_key("alpine"));
+    assert_eq!(org.robots.keys().collect::<Vec<_>>(), ["writer"]);
+    assert!(org.teams["maintainers"].members.is_empty());
+    // Nested repository names and robot members are split in path segments, not escaped
+    let mut deletes: Vec<String> = quay
+        .state()
+        .requests
+        .iter()
+        .filter(|r| r.starts_with("DELETE"))
+        .cloned()
+        .collect();
+    deletes.sort();
+    assert_eq!(
+        deletes,
+        [
+            "DELETE /api/v1/organization/exampleorg/robots/reader",
+            "DELETE /api/v1/organization/exampleorg/team/maintainers/members/exampleorg+writer",
+            "DELETE /api/v1/organization/exampleorg/team/maintainers/members/valeidm",
+            "DELETE /api/v1/repository/exampleorg/nested/demorepo",
+        ]
+    );
+    assert!(quay
+        .organization("exampleorg2")
+        .unwrap()
+        .robots
+        .contains_key("reader"));
+}
+
+#[tokio::test]
+async fn delete_of_missing_objects_fails() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let (success, report) = workspace
+        .qcli_report(&["delete", "--org", "exampleorg", "--robot", "reader"])
+        .await;
+
+    assert!(!success);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["operations"][0]["status_code"], 404);
+}
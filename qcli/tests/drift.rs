@@ -0,0 +1,120 @@
+mod common;
+
+use common::{FakeQuay, Workspace, MIRROR_PASSWORD, TOKEN};
+use serde_json::Value;
+use std::fs;
+
+/// Endpoint refusing connections.
+const UNREACHABLE_ENDPOINT: &str = "127.0.0.1:1";
+
+#[tokio::test]
+async fn drift_reports_unreachable_organizations_as_unchecked() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization3.yaml"],
+    );
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+
+    workspace.edit_yaml(
+        "example-organization3.yaml",
+        &format!("quay_endpoint: \"{}\"", quay.endpoint()),
+        &format!("quay_endpoint: \"{}\"", UNREACHABLE_ENDPOINT),
+    );
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token: {}
+- quay_endpoint: \"{}\"
+  quay_token: {}
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password: {}
+",
+        quay.endpoint(),
+        TOKEN,
+        UNREACHABLE_ENDPOINT,
+        TOKEN,
+        MIRROR_PASSWORD
+    ));
+
+    // Without the preflight checks the organization fails while its live state is read
+    let output = workspace
+        .qcli(&["--skip-preflight", "--max-attempts", "1", "drift"], "")
+        .await;
+
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_str(
+        &fs::read_to_string(workspace.path().join("qcli-drift.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report["drift"], false);
+    assert_eq!(
+        report["organizations"][0]["quay_organization"],
+        "exampleorg"
+    );
+    assert_eq!(report["unchecked"][0]["quay_organization"], "exampleorg3");
+    assert_eq!(
+        report["unchecked"][0]["quay_endpoint"],
+        UNREACHABLE_ENDPOINT
+    );
+}
+
+#[tokio::test]
+async fn drift_reports_disabled_and_undeclared_mirrors() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+
+    let output = workspace.qcli(&["drift"], "").await;
+    assert_eq!(output.status.code(), Some(0));
+
+    // Mirror disabled in Quay, and a mirror configured on a repository without mirror_params
+    {
+        let mut state = quay.state();
+        let repositories = &mut state
+            .organizations
+            .get_mut("exampleorg")
+            .unwrap()
+            .repositories;
+        let mut mirror = repositories["alpine"].mirror.clone().unwrap();
+        mirror["is_enabled"] = false.into();
+        repositories.get_mut("alpine").unwrap().mirror = Some(mirror.clone());
+        let demorepo = repositories.get_mut("demorepo").unwrap();
+        demorepo.state = "MIRROR".to_string();
+        demorepo.mirror = Some(mirror);
+    }
+
+    let output = workspace.qcli(&["drift"], "").await;
+
+    assert_eq!(output.status.code(), Some(2));
+    let report: Value = serde_json::from_str(
+        &fs::read_to_string(workspace.path().join("qcli-drift.json")).unwrap(),
+    )
+    .unwrap();
+    let differences = report["organizations"][0]["differences"]
+        .as_array()
+        .unwrap();
+    assert_eq!(differences.len(), 2, "{:#}", report);
+    assert_eq!(differences[0]["drift"], "changed");
+    assert_eq!(differences[0]["object"], "repository alpine mirror");
+    assert_eq!(differences[0]["fields"][0]["field"], "is_enabled");
+    assert_eq!(differences[0]["fields"][0]["actual"], "false");
+    assert_eq!(differences[1]["drift"], "extra");
+    assert_eq!(differences[1]["object"], "repository demorepo mirror");
+
+    // Undeclared mirrors are not removed by apply
+    let output = workspace.qcli(&["plan"], "").await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Plan: 0 to add, 1 to change, 0 to remove."),
+        "{}",
+        stdout
+    );
+}
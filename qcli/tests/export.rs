@@ -0,0 +1,84 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+use hyper::StatusCode;
+use std::fs;
+
+#[tokio::test]
+async fn exported_organizations_plan_no_changes() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+
+    // Exported in an empty directory, with the same login file
+    let exported = Workspace::new(&quay.endpoint(), &["none"]);
+    exported.write_login(&quay.endpoint(), TOKEN, true);
+    let output = exported.qcli(&["export"], "").await;
+    assert!(output.status.success());
+
+    let yaml =
+        fs::read_to_string(exported.path().join("yaml-files").join("exampleorg.yaml")).unwrap();
+    assert!(yaml.contains("name: nested/demorepo"), "{}", yaml);
+    assert!(yaml.contains("src_image: libpod/alpine"), "{}", yaml);
+
+    let output = exported.qcli(&["plan"], "").await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Plan: 0 to add, 0 to change, 0 to remove."),
+        "{}",
+        stdout
+    );
+
+    // Existing files are kept without --overwrite
+    fs::write(
+        exported.path().join("yaml-files").join("exampleorg.yaml"),
+        "edited",
+    )
+    .unwrap();
+    let output = exported.qcli(&["export"], "").await;
+    assert!(output.status.success());
+    let yaml =
+        fs::read_to_string(exported.path().join("yaml-files").join("exampleorg.yaml")).unwrap();
+    assert_eq!(yaml, "edited");
+}
+
+#[tokio::test]
+async fn export_continues_after_an_unreadable_organization() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+
+    let exported = Workspace::new(&quay.endpoint(), &["none"]);
+    exported.write_login(&quay.endpoint(), TOKEN, true);
+    quay.fail_next(
+        "GET organization/exampleorg/robots",
+        StatusCode::FORBIDDEN,
+        "Unauthorized",
+    );
+    let output = exported.qcli(&["export"], "").await;
+
+    assert!(!output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("Unable to read organization 'exampleorg' from"),
+        "{}",
+        logs
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 organizations could not be exported"),
+        "{}",
+        stderr
+    );
+    let files = exported.path().join("yaml-files");
+    assert!(!files.join("exampleorg.yaml").exists());
+    assert!(files.join("exampleorg2.yaml").exists());
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use common::{FakeQuay, Workspace, MIRROR_PASSWORD, PASSPHRASE, TOKEN};
+use std::fs;
+
+#[tokio::test]
+async fn login_saves_token_and_mirror_passwords() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+
+    // A token for the single endpoint, then a password for each mirror user
+    let stdin = format!("{}\n{}\n{}\n", TOKEN, MIRROR_PASSWORD, MIRROR_PASSWORD);
+    let output = workspace.qcli(&["login"], &stdin).await;
+
+    assert!(output.status.success());
+    // Prompts go to stderr, as stdin is not a terminal
+    let prompt = format!("Please insert token for {}", quay.endpoint());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&prompt));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains(&prompt));
+    let login = fs::read_to_string(workspace.login_file()).unwrap();
+    assert!(login.contains("qcli_login_store: 1"));
+    assert!(!login.contains(&quay.endpoint()));
+    assert!(!login.contains(TOKEN));
+    assert!(!login.contains(MIRROR_PASSWORD));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(workspace.login_file())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // The saved login is used by the next runs
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg").is_some());
+    assert!(quay.organization("exampleorg2").is_some());
+}
+
+#[tokio::test]
+async fn login_with_wrong_token_is_rejected_by_quay() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+
+    // The token is checked before saving it
+    let stdin = format!("wrong-token\n{}\n", MIRROR_PASSWORD);
+    let output = workspace.qcli(&["login"], &stdin).await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Tokens not valid for"), "{}", stderr);
+    assert!(!workspace.login_file().exists());
+
+    let output = workspace.qcli(&["--skip-preflight", "login"], &stdin).await;
+    assert!(output.status.success());
+
+    let (success, report) = workspace.qcli_report(&["--skip-preflight", "create"]).await;
+    assert!(!success);
+    assert_eq!(report["operations"][0]["status_code"], 401);
+    assert_eq!(quay.count_requests("POST"), 1);
+}
+
+#[tokio::test]
+async fn login_store_is_not_opened_with_a_wrong_passphrase() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+
+    let stdin = format!("{}\n{}\n", TOKEN, MIRROR_PASSWORD);
+    assert!(workspace.qcli(&["login"], &stdin).await.status.success());
+
+    let workspace = workspace.with_env("QCLI_LOGIN_PASSPHRASE", "wrong-passphrase");
+    let output = workspace.qcli(&["create"], "").await;
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(log.contains("wrong passphrase"), "{}", log);
+    assert!(quay.organization("exampleorg").is_none());
+}
+
+#[tokio::test]
+async fn login_rekey_encrypts_plain_text_login_file() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"])
+        .with_env("QCLI_LOGIN_NEW_PASSPHRASE", "new-passphrase");
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let output = workspace.qcli(&["login", "--rekey"], "").await;
+    assert!(output.status.success());
+    let login = fs::read_to_string(workspace.login_file()).unwrap();
+    assert!(login.contains("qcli_login_store: 1"));
+    assert!(!login.contains(TOKEN));
+
+    // The old passphrase no longer opens the store
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(!output.status.success());
+
+    let workspace = workspace.with_env("QCLI_LOGIN_PASSPHRASE", "new-passphrase");
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg").is_some());
+
+    // Rekey again, from the new passphrase back to the default one
+    let workspace = workspace.with_env("QCLI_LOGIN_NEW_PASSPHRASE", PASSPHRASE);
+    assert!(workspace
+        .qcli(&["login", "--rekey"], "")
+        .await
+        .status
+        .success());
+    let workspace = workspace.with_env("QCLI_LOGIN_PASSPHRASE", PASSPHRASE);
+    assert!(workspace
+        .qcli(&["login", "--show"], "")
+        .await
+        .status
+        .success());
+}
+
+#[tokio::test]
+async fn login_show_masks_tokens_and_passwords() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+
+    let stdin = format!("{}\n{}\n{}\n", TOKEN, MIRROR_PASSWORD, MIRROR_PASSWORD);
+    assert!(workspace.qcli(&["login"], &stdin).await.status.success());
+
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    assert!(output.status.success());
+    let shown = String::from_utf8_lossy(&output.stdout);
+    assert!(shown.contains(&quay.endpoint()), "{}", shown);
+    assert!(shown.contains("valeidm"));
+    assert!(shown.contains("quay_token: \"********oken\""), "{}", shown);
+    assert!(!shown.contains(TOKEN));
+    assert!(!shown.contains(MIRROR_PASSWORD));
+}
+
+#[tokio::test]
+async fn login_non_interactive_reads_json_document_from_stdin() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+
+    let document = serde_json::json!({
+        "quay_endpoint_login": [{"quay_endpoint": quay.endpoint(), "quay_token": TOKEN}],
+        "mirror_repository": [{
+            "organization": "exampleorg",
+            "repository": "alpine",
+            "ext_registry_username": "valeidm",
+            "ext_registry_password": MIRROR_PASSWORD
+        }]
+    });
+    let output = workspace
+        .qcli(&["login", "--non-interactive"], &document.to_string())
+        .await;
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Please insert"));
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let organization = quay.organization("exampleorg").unwrap();
+    assert_eq!(
+        organization.repositories["alpine"]
+            .mirror_password
+            .as_deref(),
+        Some(MIRROR_PASSWORD)
+    );
+}
+
+#[tokio::test]
+async fn login_non_interactive_fails_on_missing_secrets() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+
+    let document = format!(
+        "quay_endpoint_login:\n- quay_endpoint: \"{}\"\n  quay_token: {}\n",
+        quay.endpoint(),
+        TOKEN
+    );
+    let output = workspace
+        .qcli(&["login", "--non-interactive"], &document)
+        .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("password for user 'valeidm', repository 'alpine'"),
+        "{}",
+        stderr
+    );
+    assert!(!workspace.login_file().exists());
+}
+
+#[tokio::test]
+async fn login_merge_asks_only_missing_secrets() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+    // exampleorg2 was added after the last login
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:
+- quay_endpoint: \"{}\"
+  quay_token: {}
+  rate_limit: 500
+mirror_repository:
+- organization: exampleorg
+  repository: alpine
+  ext_registry_username: valeidm
+  ext_registry_password: {}
+",
+        quay.endpoint(),
+        TOKEN,
+        MIRROR_PASSWORD
+    ));
+
+    let output = workspace
+        .qcli(&["login", "--merge"], "hola-password\n")
+        .await;
+    assert!(output.status.success());
+    let prompts = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(prompts.matches("Please insert").count(), 1, "{}", prompts);
+    assert!(prompts.contains("user 'hola'"));
+
+    let output = workspace.qcli(&["login", "--show"], "").await;
+    let shown = String::from_utf8_lossy(&output.stdout);
+    assert!(shown.contains("rate_limit: 500"), "{}", shown);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let repositories = |name: &str| quay.organization(name).unwrap().repositories;
+    assert_eq!(
+        repositories("exampleorg")["alpine"]
+            .mirror_password
+            .as_deref(),
+        Some(MIRROR_PASSWORD)
+    );
+    assert_eq!(
+        repositories("exampleorg2")["alpine"]
+            .mirror_password
+            .as_deref(),
+        Some("hola-password")
+    );
+}
+
+#[tokio::test]
+async fn login_keeps_existing_file_unless_recreation_is_confirmed() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let login = fs::read_to_string(workspace.login_file()).unwrap();
+
+    let output = workspace.qcli(&["login"], "no\n").await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("--merge"));
+    assert_eq!(fs::read_to_string(workspace.login_file()).unwrap(), login);
+
+    // No answer at all
+    let output = workspace.qcli(&["login"], "").await;
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(workspace.login_file()).unwrap(), login);
+
+    let stdin = format!("yes\n{}\n{}\n", TOKEN, MIRROR_PASSWORD);
+    let output = workspace.qcli(&["login"], &stdin).await;
+    assert!(output.status.success());
+    assert!(fs::read_to_string(workspace.login_file())
+        .unwrap()
+        .contains("qcli_login_store: 1"));
+}
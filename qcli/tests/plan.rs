@@ -0,0 +1,104 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+use hyper::StatusCode;
+
+#[tokio::test]
+async fn apply_reconciles_until_plan_has_no_changes() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let output = workspace.qcli(&["plan"], "").await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Plan: 0 to add"), "{}", stdout);
+    // Planning changes nothing
+    assert_eq!(quay.count_requests("POST"), 0);
+    assert_eq!(quay.count_requests("PUT"), 0);
+
+    // The plan is printed on stderr, stdout is left to the run report
+    let (success, report) = workspace.qcli_report(&["apply", "--auto-approve"]).await;
+    assert!(success, "{}", report);
+    assert_eq!(report["failed"], 0);
+    assert!(quay.organization("exampleorg").is_some());
+
+    let output = workspace.qcli(&["plan"], "").await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Plan: 0 to add, 0 to change, 0 to remove."),
+        "{}",
+        stdout
+    );
+}
+
+#[tokio::test]
+async fn apply_without_terminal_requires_auto_approve() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+
+    let output = workspace
+        .qcli(&["--output", "json", "apply"], "yes\n")
+        .await;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("use --auto-approve"), "{}", stderr);
+    assert!(output.stdout.is_empty());
+    assert!(quay.organization("exampleorg").is_none());
+}
+
+#[tokio::test]
+async fn apply_fails_on_objects_created_meanwhile() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    // The repository is created by someone else between the plan and its creation
+    quay.fail_next(
+        "POST repository",
+        StatusCode::BAD_REQUEST,
+        "Repository already exists",
+    );
+
+    let (success, report) = workspace.qcli_report(&["apply", "--auto-approve"]).await;
+
+    assert!(!success);
+    assert_eq!(report["unchanged"], 0);
+    let failed: Vec<_> = report["operations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|o| o["status"] == "failed")
+        .collect();
+    assert_eq!(failed.len(), 1, "{}", report);
+    assert_eq!(failed[0]["object_type"], "repository");
+    assert_eq!(failed[0]["status_code"], 400);
+    assert_eq!(
+        failed[0]["message"],
+        "400 Bad Request: Repository already exists"
+    );
+    let description = failed[0]["description"].as_str().unwrap();
+    assert!(
+        description.starts_with("Creating repository"),
+        "{}",
+        description
+    );
+
+    quay.fail_next(
+        "POST repository",
+        StatusCode::BAD_REQUEST,
+        "Repository already exists",
+    );
+    let output = workspace
+        .qcli(&["--output", "junit", "apply", "--auto-approve"], "")
+        .await;
+    assert!(!output.status.success());
+    let junit = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        junit.contains("<failure message=\"400 Bad Request: Repository already exists\"/>"),
+        "{}",
+        junit
+    );
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+
+/// Number of requests changing something on Quay.
+fn writes(quay: &FakeQuay) -> usize {
+    ["POST", "PUT", "DELETE"]
+        .iter()
+        .map(|method| quay.count_requests(method))
+        .sum()
+}
+
+async fn created_workspace(quay: &FakeQuay) -> Workspace {
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    quay.state().requests.clear();
+    workspace
+}
+
+fn preflight_errors(report: &serde_json::Value) -> Vec<&str> {
+    report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["message"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn create_skips_organization_the_token_can_not_administer() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+    quay.remove_scope("org:admin");
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(!success);
+    assert_eq!(preflight_errors(&report), ["Preflight: missing org:admin"]);
+    assert_eq!(writes(&quay), 0);
+}
+
+#[tokio::test]
+async fn create_reports_missing_repository_scopes() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+    quay.remove_scope("repo:create");
+    quay.remove_scope("repo:admin");
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(!success);
+    assert_eq!(
+        preflight_errors(&report),
+        [
+            "Preflight: missing repo:create",
+            "Preflight: missing repo:admin"
+        ]
+    );
+    assert_eq!(writes(&quay), 0);
+}
+
+#[tokio::test]
+async fn delete_needs_only_organization_admin() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+    quay.remove_scope("repo:create");
+
+    let (success, report) = workspace.qcli_report(&["delete"]).await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg").is_none());
+}
+
+#[tokio::test]
+async fn plan_only_warns_about_missing_scopes() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = created_workspace(&quay).await;
+    quay.remove_scope("repo:create");
+
+    let output = workspace.qcli(&["plan"], "").await;
+
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(log.contains("WARN"), "{}", log);
+    assert!(log.contains("missing repo:create"), "{}", log);
+}
+
+#[tokio::test]
+async fn login_reports_organizations_the_token_can_administer() {
+    let quay = FakeQuay::start(TOKEN).await;
+    created_workspace(&quay).await;
+    // exampleorg exists, exampleorg2 does not
+    let workspace = Workspace::new(
+        &quay.endpoint(),
+        &["example-organization.yaml", "example-organization2.yaml"],
+    );
+
+    let stdin = format!(
+        "{}\n{}\n{}\n",
+        TOKEN,
+        common::MIRROR_PASSWORD,
+        common::MIRROR_PASSWORD
+    );
+    let output = workspace.qcli(&["login"], &stdin).await;
+
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        log.contains("Organization 'exampleorg' on '")
+            && log.contains("the token of 'admin' can administer it"),
+        "{}",
+        log
+    );
+    assert!(log.contains("not found, 'admin' will create it"), "{}", log);
+    assert_eq!(writes(&quay), 0);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::fake_quay::{Repository, Team};
+use common::{FakeQuay, Workspace, TOKEN};
+
+/// Adds objects not declared in example-organization.yaml to exampleorg.
+fn add_unmanaged_objects(quay: &FakeQuay) {
+    let mut state = quay.state();
+    let org = state.organizations.get_mut("exampleorg").unwrap();
+    org.robots.insert("stale".to_string(), String::new());
+    org.teams.insert(
+        "staleteam".to_string(),
+        Team {
+            role: "member".to_string(),
+            ..Default::default()
+        },
+    );
+    org.teams
+        .get_mut("maintainers")
+        .unwrap()
+        .members
+        .insert("intruder".to_string());
+    org.repositories.insert(
+        "stalerepo".to_string(),
+        Repository {
+            state: "NORMAL".to_string(),
+            ..Default::default()
+        },
+    );
+    org.repositories
+        .get_mut("alpine")
+        .unwrap()
+        .user_permissions
+        .insert("intruder".to_string(), "read".to_string());
+}
+
+#[tokio::test]
+async fn prune_removes_only_the_listed_object_types() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    add_unmanaged_objects(&quay);
+
+    // quay_prune: false keeps every unmanaged object
+    workspace.edit_yaml(
+        "example-organization.yaml",
+        "#quay_prune: true",
+        "quay_prune: false",
+    );
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let org = quay.organization("exampleorg").unwrap();
+    assert!(org.repositories["alpine"]
+        .user_permissions
+        .contains_key("intruder"));
+
+    // Without quay_prune only the repository permissions are pruned
+    workspace.edit_yaml("example-organization.yaml", "quay_prune: false", "");
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let org = quay.organization("exampleorg").unwrap();
+    assert!(!org.repositories["alpine"]
+        .user_permissions
+        .contains_key("intruder"));
+    assert!(org.robots.contains_key("stale"));
+    assert!(org.teams.contains_key("staleteam"));
+    assert!(org.teams["maintainers"].members.contains("intruder"));
+    assert!(org.repositories.contains_key("stalerepo"));
+
+    workspace.edit_yaml(
+        "example-organization.yaml",
+        "#quay_prune_objects:\n#  - robots\n#  - teams\n#  - team_members\n",
+        "quay_prune: true\nquay_prune_objects:\n  - robots\n  - team_members\n#  - teams\n",
+    );
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+    assert!(success, "{}", report);
+    let org = quay.organization("exampleorg").unwrap();
+    assert!(!org.robots.contains_key("stale"));
+    assert!(!org.teams["maintainers"].members.contains("intruder"));
+    assert!(org.teams.contains_key("staleteam"));
+    assert!(org.repositories.contains_key("stalerepo"));
+    // Declared objects are kept
+    assert_eq!(org.robots.keys().collect::<Vec<_>>(), ["reader", "writer"]);
+    assert!(org.teams["maintainers"].members.contains("valeidm"));
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::fake_quay::Fault;
+use common::{FakeQuay, Workspace, TOKEN};
+use hyper::StatusCode;
+use std::time::{Duration, SystemTime};
+
+fn unavailable(status: StatusCode, retry_after: String) -> Fault {
+    Fault {
+        request: "POST organization".to_string(),
+        status,
+        message: "Try again later".to_string(),
+        headers: vec![("Retry-After".to_string(), retry_after)],
+    }
+}
+
+#[tokio::test]
+async fn throttled_requests_wait_retry_after_up_to_max_delay() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization3.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    let tomorrow = SystemTime::now() + Duration::from_secs(86400);
+    quay.state().faults.extend([
+        unavailable(StatusCode::TOO_MANY_REQUESTS, "1".to_string()),
+        unavailable(StatusCode::SERVICE_UNAVAILABLE, "86400".to_string()),
+        unavailable(
+            StatusCode::SERVICE_UNAVAILABLE,
+            httpdate::fmt_http_date(tomorrow),
+        ),
+    ]);
+
+    let output = workspace
+        .qcli(&["--retry-max-delay", "2000", "create"], "")
+        .await;
+
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", logs);
+    for retry in [
+        "status code 429 Too Many Requests, retrying in 1.0 seconds (attempt 2/4)",
+        // A day long Retry-After, as seconds or as HTTP date, waits the max delay
+        "status code 503 Service Unavailable, retrying in 2.0 seconds (attempt 3/4)",
+        "status code 503 Service Unavailable, retrying in 2.0 seconds (attempt 4/4)",
+    ] {
+        assert!(logs.contains(retry), "{}: {}", retry, logs);
+    }
+    let posts = quay
+        .state()
+        .requests
+        .iter()
+        .filter(|r| r.as_str() == "POST /api/v1/organization/")
+        .count();
+    assert_eq!(posts, 4);
+    assert!(quay.organization("exampleorg3").is_some());
+}
+
+#[tokio::test]
+async fn requests_still_throttled_after_max_attempts_report_the_last_response() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["example-organization3.yaml"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, true);
+    quay.state().faults.extend([
+        unavailable(StatusCode::TOO_MANY_REQUESTS, "0".to_string()),
+        unavailable(StatusCode::TOO_MANY_REQUESTS, "0".to_string()),
+    ]);
+
+    let (success, report) = workspace
+        .qcli_report(&["--max-attempts", "2", "create"])
+        .await;
+
+    assert!(!success);
+    let operation = &report["operations"][0];
+    assert_eq!(operation["object_type"], "organization");
+    assert_eq!(operation["status"], "failed");
+    // The last response is reported with the error sent by Quay
+    assert_eq!(operation["status_code"], 429);
+    assert_eq!(
+        operation["message"],
+        "429 Too Many Requests: Try again later"
+    );
+    assert!(quay.organization("exampleorg3").is_none());
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::Workspace;
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+
+#[tokio::test]
+async fn schema_describes_the_organization_yaml_files() {
+    let workspace = Workspace::new("quay.example.com", &["none"]);
+
+    let output = workspace.qcli(&["schema"], "").await;
+    assert!(output.status.success());
+    // Only the schema is printed, without logs
+    let schema: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    let definitions = &schema["definitions"];
+    for definition in [
+        "Repository",
+        "MirrorParams",
+        "Permissions",
+        "Team",
+        "RobotDetails",
+    ] {
+        assert_eq!(
+            definitions[definition]["additionalProperties"],
+            Value::Bool(false),
+            "{}",
+            definition
+        );
+    }
+    let enum_values = |property: &Value| property["anyOf"][0]["enum"].clone();
+    assert_eq!(
+        enum_values(&definitions["Repository"]["properties"]["visibility"]),
+        serde_json::json!(["public", "private"])
+    );
+    assert_eq!(
+        enum_values(&definitions["UserElement"]["properties"]["role"]),
+        serde_json::json!(["read", "write", "admin"])
+    );
+    assert_eq!(
+        enum_values(&definitions["Team"]["properties"]["role"]),
+        serde_json::json!(["member", "creator", "admin"])
+    );
+    assert!(definitions["MirrorParams"]["properties"]["sync_interval"]["description"].is_string());
+    // The vars block of the templates is part of the file
+    assert_eq!(schema["properties"]["vars"]["type"], "object");
+    let robots = definitions["Permissions"]["properties"]["robots"]["description"]
+        .as_str()
+        .unwrap();
+    assert!(robots.contains("prefix is removed"), "{}", robots);
+    let quay_prune = schema["properties"]["quay_prune"]["description"]
+        .as_str()
+        .unwrap();
+    assert!(
+        quay_prune.contains("Default to deleting only the repository permissions"),
+        "{}",
+        quay_prune
+    );
+
+    let output = workspace
+        .qcli(&["schema", "--file", "qcli-schema.json"], "")
+        .await;
+    assert!(output.status.success());
+    let written: Value = serde_json::from_str(
+        &fs::read_to_string(workspace.path().join("qcli-schema.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(written, schema);
+}
+
+#[tokio::test]
+async fn strict_rejects_unknown_fields() {
+    let workspace = Workspace::new("quay.example.com", &["example-organization3.yaml"]);
+    workspace.edit_yaml(
+        "example-organization3.yaml",
+        "    description: \"example of repository with mirror feature disabled\"\n",
+        "    description: \"example of repository with mirror feature disabled\"\n    visiblity: private\n",
+    );
+    workspace.edit_yaml(
+        "example-organization3.yaml",
+        "        sync_interval: 86400\n",
+        "        sync_interval: 86400\n        sync_intervall: 3600\n",
+    );
+    workspace.edit_yaml(
+        "example-organization3.yaml",
+        "quay_organization_role_email: \"\"\n",
+        "quay_organization_role_email: \"\"\nvars:\n  stage: prod\n",
+    );
+
+    // Unknown fields are ignored by default
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(output.status.success());
+
+    let output = workspace.qcli(&["--strict", "check"], "").await;
+    assert!(!output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains(
+            "example-organization3.yaml: unknown field 'repositories.0.mirror_params.sync_intervall', 'repositories.1.visiblity'"
+        ),
+        "{}",
+        logs
+    );
+
+    // strict of the config file
+    fs::create_dir_all(workspace.config_dir()).unwrap();
+    fs::write(workspace.config_dir().join("qcli.toml"), "strict = true\n").unwrap();
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(!output.status.success());
+
+    // The option and its env variable override the config file
+    let output = workspace.qcli(&["--strict=false", "check"], "").await;
+    assert!(output.status.success());
+    let workspace = workspace.with_env("QCLI_STRICT", "false");
+    let output = workspace.qcli(&["check"], "").await;
+    assert!(output.status.success());
+}
+
+#[test]
+fn only_schema_runs_without_a_yaml_directory() {
+    let workspace = Workspace::new("quay.example.com", &["none"]);
+    let qcli = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_qcli"))
+            .current_dir(workspace.path())
+            .args(args)
+            .env_remove("QCLI_CONFIG")
+            .env("XDG_CONFIG_HOME", workspace.path().join("config"))
+            .output()
+            .unwrap()
+    };
+
+    let output = qcli(&["schema"]);
+    assert!(output.status.success());
+    serde_json::from_slice::<Value>(&output.stdout).unwrap();
+
+    let output = qcli(&["check"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--dir <DIR>"), "{}", stderr);
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::{FakeQuay, Workspace, TOKEN};
+
+/// Organization yaml of a team, rendered with the vars block, --var and env variables.
+fn team_template(quay_endpoint: &str) -> String {
+    format!(
+        "---
+vars:
+  team: Payments
+  stage: prod
+quay_endpoint: \"{}\"
+quay_validate_certs: \"yes\"
+quay_organization: \"{{{{ team | lower }}}}-{{{{ stage }}}}\"
+quay_organization_role_name: \"{{{{ quay_organization }}}}\"
+quay_organization_role_email: \"{{{{ env.QCLI_TEST_EMAIL | default('quay@example.com') }}}}\"
+# Comments are not rendered: {{{{ undefined }}}}
+repositories:
+  - name: \"{{{{ team | lower }}}}-app\"
+    mirror: false
+robots:
+  - name: \"{{{{ env.QCLI_TEST_ROBOT }}}}\"
+    desc: \"Robot of {{{{ quay_organization }}}}\"
+teams: []
+",
+        quay_endpoint
+    )
+}
+
+#[tokio::test]
+async fn templates_are_rendered_before_loading() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace =
+        Workspace::new(&quay.endpoint(), &["none"]).with_env("QCLI_TEST_ROBOT", "deployer");
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+    workspace.write_yaml("payments.yaml", &team_template(&quay.endpoint()));
+
+    let (success, report) = workspace
+        .qcli_report(&["--var", "stage=staging", "create"])
+        .await;
+
+    assert!(success, "{}", report);
+    let org = quay.organization("payments-staging").unwrap();
+    assert!(org.repositories.contains_key("payments-app"));
+    assert!(org.robots.contains_key("deployer"));
+}
+
+#[tokio::test]
+async fn template_errors_point_at_the_original_line() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &["none"]);
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+    workspace.write_yaml("payments.yaml", &team_template(&quay.endpoint()));
+
+    // QCLI_TEST_ROBOT is not set
+    let output = workspace.qcli(&["check"], "").await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("payments.yaml: line 15: 'env.QCLI_TEST_ROBOT' is not defined"),
+        "{}",
+        logs
+    );
+
+    // Yaml errors of the rendered file keep the lines of the template
+    workspace.edit_yaml("payments.yaml", "teams: []", "teams: \"{{ team }}\"");
+    let workspace = workspace.with_env("QCLI_TEST_ROBOT", "deployer");
+    let output = workspace.qcli(&["check"], "").await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("payments.yaml: teams: invalid type"),
+        "{}",
+        logs
+    );
+    assert!(logs.contains("at line 17"), "{}", logs);
+
+    workspace.edit_yaml(
+        "payments.yaml",
+        "  stage: prod",
+        "  stage: \"{{ quay_organization }}\"",
+    );
+    let output = workspace.qcli(&["check"], "").await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains(
+            "line 4: Circular reference: quay_organization -> stage -> quay_organization"
+        ),
+        "{}",
+        logs
+    );
+}
+
+#[tokio::test]
+async fn template_values_are_escaped_for_their_quoted_string() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace =
+        Workspace::new(&quay.endpoint(), &["none"]).with_env("QCLI_TEST_ROBOT", "deployer");
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+    workspace.write_yaml("payments.yaml", &team_template(&quay.endpoint()));
+    workspace.edit_yaml(
+        "payments.yaml",
+        "    desc: \"Robot of {{ quay_organization }}\"",
+        "    desc: \"Robot of {{ note }}\"",
+    );
+    workspace.edit_yaml(
+        "payments.yaml",
+        "    mirror: false",
+        "    description: '{{ note }}' # {{ note }}\n    mirror: false",
+    );
+    let note = r#"it's "quoted": \ # and not a comment"#;
+    let var = format!("note={}", note);
+
+    let (success, report) = workspace.qcli_report(&["--var", &var, "create"]).await;
+
+    assert!(success, "{}", report);
+    let org = quay.organization("payments-prod").unwrap();
+    assert_eq!(org.robots["deployer"], format!("Robot of {}", note));
+    assert_eq!(org.repositories["payments-app"].description, note);
+
+    // Unquoted values could add keys to the file
+    workspace.edit_yaml(
+        "payments.yaml",
+        "quay_validate_certs: \"yes\"",
+        "quay_validate_certs: {{ note }}",
+    );
+    let output = workspace.qcli(&["--var", &var, "check"], "").await;
+    assert!(!output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("payments.yaml: line 6: 'note' is not in a quoted string"),
+        "{}",
+        logs
+    );
+}
+
+#[tokio::test]
+async fn quoted_strings_can_span_several_lines() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace =
+        Workspace::new(&quay.endpoint(), &["none"]).with_env("QCLI_TEST_ROBOT", "deployer");
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+    workspace.write_yaml("payments.yaml", &team_template(&quay.endpoint()));
+    workspace.edit_yaml(
+        "payments.yaml",
+        "    desc: \"Robot of {{ quay_organization }}\"",
+        "    desc: 'Robot of the team''s\n      {{ note }}'",
+    );
+    workspace.edit_yaml(
+        "payments.yaml",
+        "    mirror: false",
+        "    description: \"App of 'payments'\n      {{ note }}\"\n    mirror: false",
+    );
+    let note = r#"it's "quoted""#;
+    let var = format!("note={}", note);
+
+    let (success, report) = workspace.qcli_report(&["--var", &var, "create"]).await;
+
+    assert!(success, "{}", report);
+    let org = quay.organization("payments-prod").unwrap();
+    assert_eq!(
+        org.robots["deployer"],
+        format!("Robot of the team's {}", note)
+    );
+    assert_eq!(
+        org.repositories["payments-app"].description,
+        format!("App of 'payments' {}", note)
+    );
+}
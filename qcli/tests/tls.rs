@@ -0,0 +1,128 @@
+mod common;
+
+use common::{tls_fixture, FakeQuay, Workspace, TOKEN};
+
+const ORGANIZATION: &str = "example-organization3.yaml";
+
+#[tokio::test]
+async fn untrusted_certificate_is_rejected() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]).without_ca_bundle();
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(!success);
+    let message = report["errors"][0]["message"].as_str().unwrap();
+    assert!(
+        message.starts_with("Preflight: Unable to reach"),
+        "{}",
+        message
+    );
+    assert!(quay.state().requests.is_empty());
+
+    let (success, report) = workspace.qcli_report(&["--skip-preflight", "create"]).await;
+
+    assert!(!success);
+    assert_eq!(report["failed"], 1);
+    assert!(report["operations"][0]["status_code"].is_null());
+    assert!(quay.state().requests.is_empty());
+}
+
+#[tokio::test]
+async fn quay_validate_certs_disables_verification() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]).without_ca_bundle();
+    workspace.edit_yaml(
+        ORGANIZATION,
+        "quay_validate_certs: \"yes\"",
+        "quay_validate_certs: \"no\"",
+    );
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg3").is_some());
+}
+
+#[tokio::test]
+async fn tls_verify_overrides_quay_validate_certs() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]).without_ca_bundle();
+    workspace.edit_yaml(
+        ORGANIZATION,
+        "quay_validate_certs: \"yes\"",
+        "quay_validate_certs: \"no\"",
+    );
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+
+    let (success, _) = workspace
+        .qcli_report(&["--tls-verify", "true", "create"])
+        .await;
+    assert!(!success);
+    assert!(quay.state().requests.is_empty());
+
+    workspace.edit_yaml(
+        ORGANIZATION,
+        "quay_validate_certs: \"no\"",
+        "quay_validate_certs: \"yes\"",
+    );
+    let (success, report) = workspace
+        .qcli_report(&["--tls-verify", "false", "create"])
+        .await;
+    assert!(success, "{}", report);
+}
+
+#[tokio::test]
+async fn ca_file_of_the_endpoint_is_trusted() {
+    let quay = FakeQuay::start(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]).without_ca_bundle();
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:\n- quay_endpoint: \"{}\"\n  quay_token: \"{}\"\n  ca_file: \"{}\"\n",
+        quay.endpoint(),
+        TOKEN,
+        tls_fixture("ca.crt")
+    ));
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg3").is_some());
+}
+
+#[tokio::test]
+async fn mutual_tls_requires_client_certificate() {
+    let quay = FakeQuay::start_mutual_tls(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]);
+    workspace.write_login(&quay.endpoint(), TOKEN, false);
+
+    let (success, _) = workspace.qcli_report(&["create"]).await;
+    assert!(!success);
+    assert!(quay.state().requests.is_empty());
+
+    let (cert, key) = (tls_fixture("client.crt"), tls_fixture("client.key"));
+    let (success, report) = workspace
+        .qcli_report(&["--client-cert", &cert, "--client-key", &key, "create"])
+        .await;
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg3").is_some());
+}
+
+#[tokio::test]
+async fn client_certificate_of_the_endpoint_is_used() {
+    let quay = FakeQuay::start_mutual_tls(TOKEN).await;
+    let workspace = Workspace::new(&quay.endpoint(), &[ORGANIZATION]);
+    workspace.write_login_yaml(&format!(
+        "quay_endpoint_login:\n- quay_endpoint: \"{}\"\n  quay_token: \"{}\"\n  client_cert: \"{}\"\n  client_key: \"{}\"\n",
+        quay.endpoint(),
+        TOKEN,
+        tls_fixture("client.crt"),
+        tls_fixture("client.key")
+    ));
+
+    let (success, report) = workspace.qcli_report(&["create"]).await;
+
+    assert!(success, "{}", report);
+    assert!(quay.organization("exampleorg3").is_some());
+}